@@ -0,0 +1,335 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::span::{SourceMap, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A span of source with an optional message attached to it. Primary labels
+/// mark the cause of a diagnostic, secondary ones add context.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: Option<String>,
+    pub primary: bool,
+}
+
+/// A problem found in a program, independent of the pass that found it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: Some(message.into()),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: Some(message.into()),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// The span of the first primary label, if there is one.
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|label| label.primary).map(|label| label.span)
+    }
+}
+
+/// Renders diagnostics against their source in the style of
+///
+/// ```text
+/// error[E0102]: Expect ';'.
+///  --> 1:10
+///   |
+/// 1 | print 1 2;
+///   |         ^ expected ';'
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Renderer {
+    color: bool,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Renderer {
+            color,
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic, source: &SourceMap) -> String {
+        let severity_style = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        };
+        let mut lines = vec![];
+
+        let mut header = diagnostic.severity.to_string();
+        if let Some(code) = diagnostic.code {
+            header.push_str(&format!("[{}]", code));
+        }
+        lines.push(format!(
+            "{}{}",
+            self.paint(&header, severity_style),
+            self.paint(&format!(": {}", diagnostic.message), BOLD)
+        ));
+
+        // Labels are drawn grouped by the line they start on, in source order.
+        let mut labels: Vec<_> = diagnostic
+            .labels
+            .iter()
+            .map(|label| (source.location(label.span.start), label))
+            .collect();
+        labels.sort_by_key(|(location, _)| (location.line, location.column));
+        let width = labels.last().map(|(location, _)| location.line.to_string().len()).unwrap_or(1);
+        let pad = " ".repeat(width);
+        let gutter = self.paint("|", BLUE);
+
+        if let Some(span) = diagnostic.primary_span().or(labels.first().map(|(_, l)| l.span)) {
+            lines.push(format!(
+                "{}{} {}",
+                pad,
+                self.paint("-->", BLUE),
+                source.location(span.start)
+            ));
+            lines.push(format!("{} {}", pad, gutter));
+        }
+
+        let mut previous_line = None;
+        for (index, (location, _)) in labels.iter().enumerate() {
+            if previous_line == Some(location.line) {
+                continue;
+            }
+            if previous_line.is_some_and(|previous| location.line > previous + 1) {
+                lines.push(self.paint("...", BLUE));
+            }
+            previous_line = Some(location.line);
+
+            let text = source.line_text(location.line).unwrap_or_default();
+            let line_number = format!("{:>width$}", location.line, width = width);
+            lines.push(
+                format!("{} {} {}", self.paint(&line_number, BLUE), gutter, text)
+                    .trim_end()
+                    .to_string(),
+            );
+
+            for (_, label) in labels[index..].iter().take_while(|(l, _)| l.line == location.line) {
+                lines.push(format!(
+                    "{} {} {}",
+                    pad,
+                    gutter,
+                    self.underline(label, location.line, text, source, severity_style)
+                ));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            lines.push(format!(
+                "{} {} {}",
+                pad,
+                self.paint("=", BLUE),
+                self.labelled("note", note)
+            ));
+        }
+        if let Some(help) = &diagnostic.help {
+            lines.push(format!(
+                "{} {} {}",
+                pad,
+                self.paint("=", BLUE),
+                self.labelled("help", help)
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Renders every diagnostic, separated by blank lines.
+    pub fn render_all(&self, diagnostics: &[Diagnostic], source: &SourceMap) -> String {
+        diagnostics
+            .iter()
+            .map(|diagnostic| self.render(diagnostic, source))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// The marker line under `text` for a label starting on `line`. Spans
+    /// reaching past the end of the line are cut off there, and empty spans
+    /// still get a single marker.
+    fn underline(
+        &self,
+        label: &Label,
+        line: usize,
+        text: &str,
+        source: &SourceMap,
+        style: &str,
+    ) -> String {
+        let line_span = source.line_span(line).unwrap_or(Span::empty(label.span.start));
+        let start = label.span.start.clamp(line_span.start, line_span.end) - line_span.start;
+        let end = label.span.end.clamp(line_span.start, line_span.end) - line_span.start;
+        let prefix = &text[..floor_boundary(text, start)];
+        let marked = &text[floor_boundary(text, start)..floor_boundary(text, end)];
+
+        // Keep tabs so that the markers line up with the source above them.
+        let indent: String = prefix.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let (marker, style) = if label.primary { ('^', style) } else { ('-', BLUE) };
+        let mut marks: String =
+            std::iter::repeat_n(marker, marked.chars().count().max(1)).collect();
+        if let Some(message) = &label.message {
+            marks.push(' ');
+            marks.push_str(message);
+        }
+        format!("{}{}", indent, self.paint(&marks, style))
+    }
+
+    fn labelled(&self, kind: &str, text: &str) -> String {
+        format!("{}: {}", self.paint(kind, BOLD), text)
+    }
+
+    fn paint(&self, text: &str, style: &str) -> String {
+        if self.color { format!("{}{}{}", style, text, RESET) } else { text.to_string() }
+    }
+}
+
+fn floor_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let source = "var a = 1;\nprint a +;\n";
+        let map = SourceMap::new(source);
+        let semicolon = source.rfind(';').unwrap();
+        let plus = source.find('+').unwrap();
+        let diagnostic = Diagnostic::error("Expect expression.")
+            .with_code("E0101")
+            .with_primary(Span::new(semicolon, semicolon + 1), "expected expression")
+            .with_secondary(Span::new(plus, plus + 1), "operator needs a right operand")
+            .with_note("the first note")
+            .with_help("add an operand");
+        assert_eq!(
+            Renderer::default().render(&diagnostic, &map),
+            "error[E0101]: Expect expression.
+ --> 2:10
+  |
+2 | print a +;
+  |         - operator needs a right operand
+  |          ^ expected expression
+  = note: the first note
+  = help: add an operand"
+        );
+    }
+
+    #[test]
+    fn test_render_multiple_lines() {
+        let source = "print \"a\tb\";\n\nvar x = \"unterminated\n";
+        let map = SourceMap::new(source);
+        let quote = source.rfind('"').unwrap();
+        let b = source.find('b').unwrap();
+        let diagnostic = Diagnostic::error("Unterminated string.")
+            .with_primary(Span::new(quote, source.len()), "string starts here")
+            .with_secondary(Span::new(b, b + 1), "unrelated");
+        assert_eq!(
+            Renderer::default().render(&diagnostic, &map),
+            "error: Unterminated string.
+ --> 3:9
+  |
+1 | print \"a\tb\";
+  |         \t- unrelated
+...
+3 | var x = \"unterminated
+  |         ^^^^^^^^^^^^^ string starts here"
+        );
+    }
+
+    #[test]
+    fn test_render_empty_span_and_color() {
+        let map = SourceMap::new("print 1");
+        let diagnostic = Diagnostic::error("Expect ';'.").with_primary(Span::empty(7), "here");
+        assert_eq!(
+            Renderer::default().render(&diagnostic, &map),
+            "error: Expect ';'.
+ --> 1:8
+  |
+1 | print 1
+  |        ^ here"
+        );
+        let colored = Renderer::new(true).render(&diagnostic, &map);
+        assert!(colored.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(colored.contains("\x1b[1;31m^ here\x1b[0m"));
+    }
+}
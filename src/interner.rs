@@ -0,0 +1,88 @@
+use std::{collections::HashMap, rc::Rc};
+
+/// A handle to interned text, such as a name or a string literal. Symbols for
+/// the same text from the same [`Interner`] are equal, so comparing and
+/// hashing them is as cheap as for the `u32` inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Symbols the interpreter looks up by name, interned up front in this order
+/// by every interner.
+const PREDEFINED: [&str; 3] = ["init", "this", "super"];
+
+impl Symbol {
+    pub const INIT: Symbol = Symbol(0);
+    pub const SUPER: Symbol = Symbol(2);
+    pub const THIS: Symbol = Symbol(1);
+}
+
+/// Maps text to symbols and back. An interner belongs to a session, such as
+/// a run of a script or a REPL, and the text in it is freed with it. Symbols
+/// are only meaningful to the interner that made them.
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            strings: vec![],
+        };
+        for text in PREDEFINED {
+            interner.intern(text);
+        }
+        interner
+    }
+
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(text) {
+            return *symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("too many symbols"));
+        let text: Rc<str> = text.into();
+        self.symbols.insert(text.clone(), symbol);
+        self.strings.push(text);
+        symbol
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Interner::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a");
+        assert_eq!(interner.intern("a"), a);
+        assert_ne!(interner.intern("b"), a);
+        assert_eq!(interner.resolve(a), "a");
+        assert_eq!(interner.intern("init"), Symbol::INIT);
+        assert_eq!(interner.intern("this"), Symbol::THIS);
+        assert_eq!(interner.resolve(Symbol::SUPER), "super");
+    }
+
+    #[test]
+    fn test_separate_interners() {
+        // Every interner starts over, and frees its text when dropped.
+        let text = {
+            let mut interner = Interner::new();
+            let name = interner.intern("name");
+            assert_eq!(Interner::new().intern("other"), name);
+            Rc::downgrade(&interner.strings[name.0 as usize])
+        };
+        assert!(text.upgrade().is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    cell::RefCell,
+    fmt::{Debug, Display, Formatter},
+    rc::Rc,
+};
+
+use crate::{
+    interner::Symbol,
+    interpreter::{
+        Interpreter, RuntimeError, class::LoxInstance, environment::Environment, value::LoxValue,
+    },
+    parser::ast::{FunctionStmt, Statement},
+    resolver::Locals,
+    scanner::OwnedToken,
+};
+
+pub trait LoxCallable {
+    fn arity(&self) -> usize;
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        arguments: Vec<LoxValue>,
+    ) -> Result<LoxValue, RuntimeError>;
+}
+
+/// A user-defined function together with the environment it was declared in.
+pub struct LoxFunction {
+    name: OwnedToken,
+    params: Vec<OwnedToken>,
+    body: Rc<Vec<Statement>>,
+    closure: Rc<RefCell<Environment>>,
+    /// The resolver's table for the program the function was declared in.
+    locals: Rc<Locals>,
+    /// Initializers always return `this`, even from a bare `return;`.
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: &FunctionStmt,
+        closure: Rc<RefCell<Environment>>,
+        locals: Rc<Locals>,
+        is_initializer: bool,
+    ) -> Self {
+        LoxFunction {
+            name: declaration.name.clone(),
+            params: declaration.params.clone(),
+            body: declaration.body.clone(),
+            closure,
+            locals,
+            is_initializer,
+        }
+    }
+
+    /// Returns a copy of this method whose closure binds `this` to
+    /// `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define(Symbol::THIS, LoxValue::Instance(instance));
+        LoxFunction {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: self.body.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            locals: self.locals.clone(),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    fn this(&self) -> LoxValue {
+        self.closure.borrow().lookup(Symbol::THIS).unwrap_or(LoxValue::Nil)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        arguments: Vec<LoxValue>,
+    ) -> Result<LoxValue, RuntimeError> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        for (param, argument) in self.params.iter().zip(arguments) {
+            environment.define(param.symbol(), argument);
+        }
+        let previous = interpreter.locals.replace(self.locals.clone());
+        let result = interpreter.execute_block(&self.body, environment);
+        interpreter.locals.replace(previous);
+        match result {
+            Ok(()) | Err(RuntimeError::Return(_)) if self.is_initializer => Ok(self.this()),
+            Ok(()) => Ok(LoxValue::Nil),
+            Err(RuntimeError::Return(value)) => Ok(value),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Debug for LoxFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxFunction({})", self.name.lexeme)
+    }
+}
+
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
+}
+
+pub type NativeFn = fn(&Interpreter, &[LoxValue]) -> Result<LoxValue, RuntimeError>;
+
+/// A function implemented in Rust and exposed to Lox code as a global.
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        arguments: Vec<LoxValue>,
+    ) -> Result<LoxValue, RuntimeError> {
+        (self.function)(interpreter, &arguments)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter},
+    rc::Rc,
+};
+
+use crate::{
+    interner::Symbol,
+    interpreter::{
+        Interpreter, RuntimeError,
+        callable::{LoxCallable, LoxFunction},
+        value::LoxValue,
+    },
+    scanner::OwnedToken,
+};
+
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Looks `name` up on this class, then on each superclass in turn.
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(&name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+}
+
+/// Calling a class constructs a new instance and runs its `init` method, if
+/// it has one.
+impl LoxCallable for Rc<LoxClass> {
+    fn arity(&self) -> usize {
+        self.find_method(Symbol::INIT).map(|init| init.arity()).unwrap_or(0)
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        arguments: Vec<LoxValue>,
+    ) -> Result<LoxValue, RuntimeError> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(self.clone())));
+        if let Some(init) = self.find_method(Symbol::INIT) {
+            init.bind(instance.clone()).call(interpreter, arguments)?;
+        }
+        Ok(LoxValue::Instance(instance))
+    }
+}
+
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxClass({})", self.name)
+    }
+}
+
+impl Display for LoxClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<Symbol, LoxValue>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Fields shadow methods; methods are bound to `instance` on access.
+    pub fn get(
+        instance: &Rc<RefCell<LoxInstance>>,
+        name: &OwnedToken,
+    ) -> Result<LoxValue, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.symbol()) {
+            return Ok(value.clone());
+        }
+        let method = instance.borrow().class.find_method(name.symbol());
+        match method {
+            Some(method) => Ok(method.bind(instance.clone()).into()),
+            None => Err(RuntimeError::UndefinedProperty(Box::new(name.clone()))),
+        }
+    }
+
+    pub fn set(&mut self, name: &OwnedToken, value: LoxValue) {
+        self.fields.insert(name.symbol(), value);
+    }
+}
+
+impl Debug for LoxInstance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxInstance({})", self.class.name)
+    }
+}
+
+impl Display for LoxInstance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,153 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    interner::Symbol,
+    interpreter::{RuntimeError, value::LoxValue},
+    scanner::OwnedToken,
+};
+
+/// A single scope of variable bindings, linked to the scope that encloses it.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, LoxValue>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Binds `name` in this scope, silently replacing any previous binding.
+    pub fn define(&mut self, name: Symbol, value: LoxValue) {
+        self.values.insert(name, value);
+    }
+
+    pub fn get(&self, name: &OwnedToken) -> Result<LoxValue, RuntimeError> {
+        self.lookup(name.symbol())
+            .ok_or_else(|| RuntimeError::UndefinedVariable(Box::new(name.clone())))
+    }
+
+    /// Like [`Environment::get`], for names that do not come from a token,
+    /// such as `this` and `super`.
+    pub fn lookup(&self, name: Symbol) -> Option<LoxValue> {
+        if let Some(value) = self.values.get(&name) {
+            return Some(value.clone());
+        }
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().lookup(name))
+    }
+
+    /// Walks `distance` scopes outwards from `environment`, as computed by the
+    /// resolver.
+    pub fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = environment.clone();
+        for _ in 0..distance {
+            let enclosing = environment.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => break,
+            }
+        }
+        environment
+    }
+
+    pub fn get_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &OwnedToken,
+    ) -> Result<LoxValue, RuntimeError> {
+        let ancestor = Environment::ancestor(environment, distance);
+        let value = ancestor.borrow().values.get(&name.symbol()).cloned();
+        value.ok_or_else(|| RuntimeError::UndefinedVariable(Box::new(name.clone())))
+    }
+
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+        name: &OwnedToken,
+        value: LoxValue,
+    ) {
+        Environment::ancestor(environment, distance).borrow_mut().define(name.symbol(), value);
+    }
+
+    pub fn assign(&mut self, name: &OwnedToken, value: LoxValue) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.symbol()) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::UndefinedVariable(Box::new(name.clone()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interner::Interner,
+        scanner::{Token, TokenType},
+        span::Span,
+    };
+
+    /// Identifier tokens for `a`, `b` and `c`.
+    fn names() -> [OwnedToken; 3] {
+        let mut interner = Interner::new();
+        ["a", "b", "c"].map(|lexeme| {
+            Token::new(TokenType::Identifier, lexeme, None, 1, 1, Span::default())
+                .to_owned_token(&mut interner)
+        })
+    }
+
+    #[test]
+    fn test_lookup_walks_enclosing_scopes() {
+        let [a, b, c] = names();
+        let global = Rc::new(RefCell::new(Environment::new()));
+        global.borrow_mut().define(a.symbol(), LoxValue::Number(1.0));
+        let mut local = Environment::with_enclosing(global.clone());
+        local.define(b.symbol(), LoxValue::Number(2.0));
+
+        assert_eq!(local.get(&a).unwrap(), LoxValue::Number(1.0));
+        assert_eq!(local.get(&b).unwrap(), LoxValue::Number(2.0));
+        assert!(matches!(local.get(&c), Err(RuntimeError::UndefinedVariable(_))));
+    }
+
+    #[test]
+    fn test_assign_updates_defining_scope() {
+        let [a, b, _] = names();
+        let global = Rc::new(RefCell::new(Environment::new()));
+        global.borrow_mut().define(a.symbol(), LoxValue::Nil);
+        let mut local = Environment::with_enclosing(global.clone());
+
+        local.assign(&a, LoxValue::Boolean(true)).unwrap();
+        assert_eq!(global.borrow().get(&a).unwrap(), LoxValue::Boolean(true));
+        assert!(local.assign(&b, LoxValue::Nil).is_err());
+    }
+
+    #[test]
+    fn test_get_at_skips_shadowing_scopes() {
+        let [a, ..] = names();
+        let global = Rc::new(RefCell::new(Environment::new()));
+        global.borrow_mut().define(a.symbol(), LoxValue::Number(1.0));
+        let local = Rc::new(RefCell::new(Environment::with_enclosing(global.clone())));
+        local.borrow_mut().define(a.symbol(), LoxValue::Number(2.0));
+
+        assert_eq!(Environment::get_at(&local, 0, &a).unwrap(), LoxValue::Number(2.0));
+        assert_eq!(Environment::get_at(&local, 1, &a).unwrap(), LoxValue::Number(1.0));
+
+        Environment::assign_at(&local, 1, &a, LoxValue::Nil);
+        assert_eq!(global.borrow().get(&a).unwrap(), LoxValue::Nil);
+        assert_eq!(local.borrow().get(&a).unwrap(), LoxValue::Number(2.0));
+    }
+}
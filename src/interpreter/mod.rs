@@ -0,0 +1,1060 @@
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    collections::HashMap,
+    fmt::Display,
+    io::Write,
+    rc::Rc,
+};
+
+use crate::{
+    interner::{Interner, Symbol},
+    interpreter::{
+        callable::{LoxFunction, NativeFn, NativeFunction},
+        class::{LoxClass, LoxInstance},
+        environment::Environment,
+        value::LoxValue,
+    },
+    parser::ast::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, ConditionalExpr, Expression,
+        ExpressionStmt, ExpressionVisitor, FunctionStmt, GetExpr, GroupingExpr, IfStmt,
+        InterpolationExpr, LiteralExpr, LogicalExpr, NodeId, PrintStmt, ReturnStmt, SetExpr,
+        Statement, StatementVisitor, SuperExpr, ThisExpr, UnaryExpr, VarStmt, VariableExpr,
+        WhileStmt,
+    },
+    resolver::Locals,
+    scanner::{LiteralValue, OwnedToken, TokenType},
+};
+
+pub mod callable;
+pub mod class;
+pub mod environment;
+pub mod natives;
+pub mod value;
+
+/// How many calls may be in progress at once. Each one takes up some of the
+/// Rust stack, so deeper recursion is reported as an error instead of
+/// crashing the host. The browser's stack is small; natively, programs run
+/// on a thread of [`STACK_SIZE`] bytes, which has room for many more.
+#[cfg(target_arch = "wasm32")]
+const MAX_CALL_DEPTH: usize = 256;
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// The stack to run the interpreter with natively, enough for
+/// [`MAX_CALL_DEPTH`] calls even in debug builds. Only the part that is
+/// used is ever committed.
+#[cfg(not(target_arch = "wasm32"))]
+pub const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+/// Runs `f` on a thread with a stack of [`STACK_SIZE`] bytes, since the
+/// main thread's stack is too small for deep recursion in Lox code.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn with_stack<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to start the interpreter thread");
+        thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+pub struct Interpreter {
+    /// The outermost scope, holding native functions and top-level
+    /// declarations.
+    globals: Rc<RefCell<Environment>>,
+    /// The innermost scope that is currently being executed.
+    environment: RefCell<Rc<RefCell<Environment>>>,
+    /// Scope distances computed by the resolver for the code that is
+    /// currently running. References missing from this table are globals.
+    /// Each program gets its own table, which the functions it declares
+    /// hold on to, so it is freed along with the last of them.
+    locals: RefCell<Rc<Locals>>,
+    /// Where `print` statements write to.
+    output: RefCell<Box<dyn Write>>,
+    /// How many calls are in progress.
+    depth: Cell<usize>,
+    /// Holds the names and string values of every program this interpreter
+    /// runs, which must be parsed with it.
+    interner: RefCell<Interner>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::with_output(std::io::stdout())
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let interpreter = Interpreter {
+            environment: RefCell::new(globals.clone()),
+            globals,
+            locals: RefCell::new(Rc::new(HashMap::new())),
+            output: RefCell::new(Box::new(output)),
+            depth: Cell::new(0),
+            interner: RefCell::new(Interner::new()),
+        };
+        for native in natives::NATIVES {
+            interpreter.define_native(native.name, native.arity, native.function);
+        }
+        interpreter
+    }
+
+    /// Exposes a Rust function to Lox code as the global `name`.
+    pub fn define_native(&self, name: &'static str, arity: usize, function: NativeFn) {
+        let native = NativeFunction {
+            name,
+            arity,
+            function,
+        };
+        let name = self.interner.borrow_mut().intern(name);
+        self.globals.borrow_mut().define(name, native.into());
+    }
+
+    /// The interner to parse programs for this interpreter with.
+    pub fn interner(&self) -> RefMut<'_, Interner> {
+        self.interner.borrow_mut()
+    }
+
+    /// Records the resolver's output for a program that is about to be
+    /// interpreted, replacing that of the previous one. Functions declared
+    /// by earlier programs keep the table they were resolved with, so a REPL
+    /// can resolve and run one line at a time.
+    pub fn resolve(&self, locals: Locals) {
+        self.locals.replace(Rc::new(locals));
+    }
+
+    pub fn interpret(&self, statements: &[Statement]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            self.execute(statement)?;
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, expression: &Expression) -> Result<LoxValue, RuntimeError> {
+        expression.accept(self)
+    }
+
+    fn execute(&self, statement: &Statement) -> Result<(), RuntimeError> {
+        statement.accept(self)
+    }
+
+    /// Executes `statements` inside `environment`, restoring the previous
+    /// scope afterwards even if execution fails.
+    pub(crate) fn execute_block(
+        &self,
+        statements: &[Statement],
+        environment: Environment,
+    ) -> Result<(), RuntimeError> {
+        let previous = self.environment.replace(Rc::new(RefCell::new(environment)));
+        let result = statements.iter().try_for_each(|statement| self.execute(statement));
+        self.environment.replace(previous);
+        result
+    }
+
+    fn look_up_variable(&self, id: NodeId, name: &OwnedToken) -> Result<LoxValue, RuntimeError> {
+        match self.locals.borrow().get(&id) {
+            Some(distance) => Environment::get_at(&self.environment(), *distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn environment(&self) -> Rc<RefCell<Environment>> {
+        self.environment.borrow().clone()
+    }
+
+    fn locals(&self) -> Rc<Locals> {
+        self.locals.borrow().clone()
+    }
+}
+
+impl StatementVisitor<(), RuntimeError> for Interpreter {
+    fn visit_block(&self, stmt: &BlockStmt) -> Result<(), RuntimeError> {
+        self.execute_block(&stmt.statements, Environment::with_enclosing(self.environment()))
+    }
+
+    fn visit_class(&self, stmt: &ClassStmt) -> Result<(), RuntimeError> {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.visit_variable(superclass)? {
+                LoxValue::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::SuperclassMustBeClass(Box::new(
+                        superclass.name.clone(),
+                    )));
+                },
+            },
+            None => None,
+        };
+        self.environment().borrow_mut().define(stmt.name.symbol(), LoxValue::Nil);
+
+        // Methods of a subclass close over an extra scope that binds `super`.
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::with_enclosing(self.environment());
+                environment.define(Symbol::SUPER, LoxValue::Class(superclass.clone()));
+                Rc::new(RefCell::new(environment))
+            },
+            None => self.environment(),
+        };
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let is_initializer = method.name.symbol() == Symbol::INIT;
+                let function =
+                    LoxFunction::new(method, closure.clone(), self.locals(), is_initializer);
+                (method.name.symbol(), Rc::new(function))
+            })
+            .collect();
+
+        let class = LoxClass::new(stmt.name.lexeme.clone(), superclass, methods);
+        self.environment().borrow_mut().assign(&stmt.name, class.into())
+    }
+
+    fn visit_expression(&self, stmt: &ExpressionStmt) -> Result<(), RuntimeError> {
+        self.evaluate(&stmt.expression)?;
+        Ok(())
+    }
+
+    fn visit_function(&self, stmt: &FunctionStmt) -> Result<(), RuntimeError> {
+        let function = LoxFunction::new(stmt, self.environment(), self.locals(), false);
+        self.environment().borrow_mut().define(stmt.name.symbol(), function.into());
+        Ok(())
+    }
+
+    fn visit_if(&self, stmt: &IfStmt) -> Result<(), RuntimeError> {
+        if self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_print(&self, stmt: &PrintStmt) -> Result<(), RuntimeError> {
+        let value = self.evaluate(&stmt.expression)?;
+        let _ = writeln!(self.output.borrow_mut(), "{}", value);
+        Ok(())
+    }
+
+    fn visit_return(&self, stmt: &ReturnStmt) -> Result<(), RuntimeError> {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => LoxValue::Nil,
+        };
+        Err(RuntimeError::Return(value))
+    }
+
+    fn visit_var(&self, stmt: &VarStmt) -> Result<(), RuntimeError> {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => LoxValue::Nil,
+        };
+        self.environment().borrow_mut().define(stmt.name.symbol(), value);
+        Ok(())
+    }
+
+    fn visit_while(&self, stmt: &WhileStmt) -> Result<(), RuntimeError> {
+        while self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.body)?;
+        }
+        Ok(())
+    }
+}
+
+impl ExpressionVisitor<LoxValue, RuntimeError> for Interpreter {
+    fn visit_assign(&self, expr: &AssignExpr) -> Result<LoxValue, RuntimeError> {
+        let value = self.evaluate(&expr.value)?;
+        match self.locals.borrow().get(&expr.id) {
+            Some(distance) => {
+                Environment::assign_at(&self.environment(), *distance, &expr.name, value.clone())
+            },
+            None => self.globals.borrow_mut().assign(&expr.name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> Result<LoxValue, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        let operator = &expr.operator;
+
+        match operator.token_type {
+            TokenType::Comma => Ok(right),
+            TokenType::EqualEqual => Ok(LoxValue::Boolean(left == right)),
+            TokenType::BangEqual => Ok(LoxValue::Boolean(left != right)),
+            TokenType::Plus => match (left, right) {
+                (LoxValue::Number(l), LoxValue::Number(r)) => Ok(LoxValue::Number(l + r)),
+                (LoxValue::String(l), LoxValue::String(r)) => Ok(LoxValue::String(l + &r)),
+                _ => Err(RuntimeError::OperandsMustBeNumbersOrStrings(Box::new(operator.clone()))),
+            },
+            _ => {
+                let (l, r) = number_operands(operator, &left, &right)?;
+                match operator.token_type {
+                    TokenType::Minus => Ok(LoxValue::Number(l - r)),
+                    TokenType::Star => Ok(LoxValue::Number(l * r)),
+                    TokenType::Slash => Ok(LoxValue::Number(l / r)),
+                    TokenType::Greater => Ok(LoxValue::Boolean(l > r)),
+                    TokenType::GreaterEqual => Ok(LoxValue::Boolean(l >= r)),
+                    TokenType::Less => Ok(LoxValue::Boolean(l < r)),
+                    TokenType::LessEqual => Ok(LoxValue::Boolean(l <= r)),
+                    _ => Err(RuntimeError::UnsupportedOperator(Box::new(operator.clone()))),
+                }
+            },
+        }
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> Result<LoxValue, RuntimeError> {
+        let callee = self.evaluate(&expr.callee)?;
+        let arguments =
+            expr.arguments.iter().map(|a| self.evaluate(a)).collect::<Result<Vec<_>, _>>()?;
+
+        let Some(callable) = callee.as_callable() else {
+            return Err(RuntimeError::NotCallable(Box::new(expr.paren.clone())));
+        };
+        if arguments.len() != callable.arity() {
+            return Err(RuntimeError::ArityMismatch {
+                paren: Box::new(expr.paren.clone()),
+                expected: callable.arity(),
+                found: arguments.len(),
+            });
+        }
+        if self.depth.get() >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow(Box::new(expr.paren.clone())));
+        }
+        self.depth.set(self.depth.get() + 1);
+        let result = callable.call(self, arguments);
+        self.depth.set(self.depth.get() - 1);
+        result
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> Result<LoxValue, RuntimeError> {
+        match self.evaluate(&expr.object)? {
+            LoxValue::Instance(instance) => LoxInstance::get(&instance, &expr.name),
+            _ => Err(RuntimeError::OnlyInstancesHaveProperties(Box::new(expr.name.clone()))),
+        }
+    }
+
+    fn visit_conditional(&self, expr: &ConditionalExpr) -> Result<LoxValue, RuntimeError> {
+        if self.evaluate(&expr.condition)?.is_truthy() {
+            self.evaluate(&expr.then_branch)
+        } else {
+            self.evaluate(&expr.else_branch)
+        }
+    }
+
+    fn visit_grouping(&self, expr: &GroupingExpr) -> Result<LoxValue, RuntimeError> {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_interpolation(&self, expr: &InterpolationExpr) -> Result<LoxValue, RuntimeError> {
+        let mut result = String::new();
+        for (index, string) in expr.strings.iter().enumerate() {
+            if let Some(LiteralValue::String(text)) = string.literal {
+                result.push_str(self.interner.borrow().resolve(text));
+            }
+            if let Some(expression) = expr.expressions.get(index) {
+                result.push_str(&self.evaluate(expression)?.to_string());
+            }
+        }
+        Ok(LoxValue::String(result))
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> Result<LoxValue, RuntimeError> {
+        let value = match expr.value {
+            LiteralValue::String(s) => LoxValue::String(self.interner.borrow().resolve(s).into()),
+            LiteralValue::Number(n) => LoxValue::Number(n),
+            LiteralValue::Boolean(b) => LoxValue::Boolean(b),
+            LiteralValue::Nil => LoxValue::Nil,
+        };
+        Ok(value)
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> Result<LoxValue, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+        let short_circuits = match expr.operator.token_type {
+            TokenType::Or => left.is_truthy(),
+            _ => !left.is_truthy(),
+        };
+        if short_circuits { Ok(left) } else { self.evaluate(&expr.right) }
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> Result<LoxValue, RuntimeError> {
+        let LoxValue::Instance(instance) = self.evaluate(&expr.object)? else {
+            return Err(RuntimeError::OnlyInstancesHaveFields(Box::new(expr.name.clone())));
+        };
+        let value = self.evaluate(&expr.value)?;
+        instance.borrow_mut().set(&expr.name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> Result<LoxValue, RuntimeError> {
+        let distance = self.locals.borrow().get(&expr.id).copied().unwrap_or_default();
+        let environment = self.environment();
+        let LoxValue::Class(superclass) =
+            Environment::get_at(&environment, distance, &expr.keyword)?
+        else {
+            return Err(RuntimeError::UndefinedVariable(Box::new(expr.keyword.clone())));
+        };
+        // `this` is always bound in the scope just inside the one binding
+        // `super`.
+        let this = Environment::ancestor(&environment, distance.saturating_sub(1))
+            .borrow()
+            .lookup(Symbol::THIS);
+        let Some(LoxValue::Instance(instance)) = this else {
+            return Err(RuntimeError::UndefinedVariable(Box::new(expr.keyword.clone())));
+        };
+        match superclass.find_method(expr.method.symbol()) {
+            Some(method) => Ok(method.bind(instance).into()),
+            None => Err(RuntimeError::UndefinedProperty(Box::new(expr.method.clone()))),
+        }
+    }
+
+    fn visit_this(&self, expr: &ThisExpr) -> Result<LoxValue, RuntimeError> {
+        self.look_up_variable(expr.id, &expr.keyword)
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> Result<LoxValue, RuntimeError> {
+        let right = self.evaluate(&expr.right)?;
+        match expr.operator.token_type {
+            TokenType::Bang => Ok(LoxValue::Boolean(!right.is_truthy())),
+            TokenType::Minus => match right {
+                LoxValue::Number(n) => Ok(LoxValue::Number(-n)),
+                _ => Err(RuntimeError::OperandMustBeNumber(Box::new(expr.operator.clone()))),
+            },
+            _ => Err(RuntimeError::UnsupportedOperator(Box::new(expr.operator.clone()))),
+        }
+    }
+
+    fn visit_variable(&self, expr: &VariableExpr) -> Result<LoxValue, RuntimeError> {
+        self.look_up_variable(expr.id, &expr.name)
+    }
+}
+
+fn number_operands(
+    operator: &OwnedToken,
+    left: &LoxValue,
+    right: &LoxValue,
+) -> Result<(f64, f64), RuntimeError> {
+    match (left, right) {
+        (LoxValue::Number(l), LoxValue::Number(r)) => Ok((*l, *r)),
+        _ => Err(RuntimeError::OperandsMustBeNumbers(Box::new(operator.clone()))),
+    }
+}
+
+/// An in-memory sink for interpreter output that can be read back after the
+/// interpreter is done with it.
+#[derive(Clone, Default)]
+pub struct CapturedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl CapturedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    /// Returns everything written so far and clears the buffer.
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.take()).into_owned()
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Errors carry their token boxed, which keeps the results passed up through
+/// every call small and so the stack that deep recursion needs.
+#[derive(Debug)]
+pub enum RuntimeError {
+    OperandMustBeNumber(Box<OwnedToken>),
+    OperandsMustBeNumbers(Box<OwnedToken>),
+    OperandsMustBeNumbersOrStrings(Box<OwnedToken>),
+    UnsupportedOperator(Box<OwnedToken>),
+    UndefinedVariable(Box<OwnedToken>),
+    NotCallable(Box<OwnedToken>),
+    OnlyInstancesHaveProperties(Box<OwnedToken>),
+    OnlyInstancesHaveFields(Box<OwnedToken>),
+    UndefinedProperty(Box<OwnedToken>),
+    SuperclassMustBeClass(Box<OwnedToken>),
+    ArityMismatch {
+        paren: Box<OwnedToken>,
+        expected: usize,
+        found: usize,
+    },
+    StackOverflow(Box<OwnedToken>),
+    /// Not an error: unwinds the Rust stack from a `return` statement up to
+    /// the function call that is returning.
+    Return(LoxValue),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::OperandMustBeNumber(token) => {
+                write!(f, "Operand must be a number.\n[line {}]", token.line)
+            },
+            RuntimeError::OperandsMustBeNumbers(token) => {
+                write!(f, "Operands must be numbers.\n[line {}]", token.line)
+            },
+            RuntimeError::OperandsMustBeNumbersOrStrings(token) => {
+                write!(f, "Operands must be two numbers or two strings.\n[line {}]", token.line)
+            },
+            RuntimeError::UnsupportedOperator(token) => {
+                write!(f, "Unsupported operator '{}'.\n[line {}]", token.lexeme, token.line)
+            },
+            RuntimeError::UndefinedVariable(token) => {
+                write!(f, "Undefined variable '{}'.\n[line {}]", token.lexeme, token.line)
+            },
+            RuntimeError::NotCallable(token) => {
+                write!(f, "Can only call functions and classes.\n[line {}]", token.line)
+            },
+            RuntimeError::OnlyInstancesHaveProperties(token) => {
+                write!(f, "Only instances have properties.\n[line {}]", token.line)
+            },
+            RuntimeError::OnlyInstancesHaveFields(token) => {
+                write!(f, "Only instances have fields.\n[line {}]", token.line)
+            },
+            RuntimeError::UndefinedProperty(token) => {
+                write!(f, "Undefined property '{}'.\n[line {}]", token.lexeme, token.line)
+            },
+            RuntimeError::SuperclassMustBeClass(token) => {
+                write!(f, "Superclass must be a class.\n[line {}]", token.line)
+            },
+            RuntimeError::ArityMismatch {
+                paren,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Expected {} arguments but got {}.\n[line {}]",
+                expected, found, paren.line
+            ),
+            RuntimeError::StackOverflow(paren) => {
+                write!(f, "Stack overflow.\n[line {}]", paren.line)
+            },
+            RuntimeError::Return(_) => write!(f, "Can't return from top-level code."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter::Trace;
+
+    use super::*;
+    use crate::{parser::Parser, resolver::Resolver};
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
+    }
+
+    fn evaluate(source: &str) -> Result<LoxValue, RuntimeError> {
+        let interpreter = Interpreter::new();
+        let tokens = crate::scanner::Scanner::new(source);
+        let expression =
+            Parser::new(tokens, &mut interpreter.interner()).parse().expect("Parsing failed.");
+        interpreter.evaluate(&expression)
+    }
+
+    fn run(source: &str) -> Result<String, RuntimeError> {
+        let output = CapturedOutput::default();
+        run_with(&Interpreter::with_output(output.clone()), source)?;
+        Ok(output.contents())
+    }
+
+    fn run_with(interpreter: &Interpreter, source: &str) -> Result<(), RuntimeError> {
+        let tokens = crate::scanner::Scanner::new(source);
+        let statements = Parser::new(tokens, &mut interpreter.interner())
+            .parse_program()
+            .into_result()
+            .expect("Parsing failed.");
+        interpreter.resolve(Resolver::new().resolve(&statements).expect("Resolving failed."));
+        interpreter.interpret(&statements)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        init_logger();
+        let result = evaluate("1 + 2 * (3 - 4) / 5 * 6").unwrap();
+        assert_eq!(result, LoxValue::Number(1.0 + 2.0 * (3.0 - 4.0) / 5.0 * 6.0));
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        init_logger();
+        let result = evaluate("\"Hello, \" + \"world!\"").unwrap();
+        assert_eq!(result, LoxValue::String("Hello, world!".to_string()));
+    }
+
+    #[test]
+    fn test_truthiness() {
+        init_logger();
+        assert_eq!(evaluate("!nil").unwrap(), LoxValue::Boolean(true));
+        assert_eq!(evaluate("!0").unwrap(), LoxValue::Boolean(false));
+        assert_eq!(evaluate("!\"\"").unwrap(), LoxValue::Boolean(false));
+        assert_eq!(evaluate("!!false").unwrap(), LoxValue::Boolean(false));
+    }
+
+    #[test]
+    fn test_equality() {
+        init_logger();
+        assert_eq!(evaluate("nil == nil").unwrap(), LoxValue::Boolean(true));
+        assert_eq!(evaluate("1 == \"1\"").unwrap(), LoxValue::Boolean(false));
+        assert_eq!(evaluate("\"a\" != \"b\"").unwrap(), LoxValue::Boolean(true));
+        assert_eq!(evaluate("2 >= 1 == true").unwrap(), LoxValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_print_statements() {
+        init_logger();
+        let output = run("print 1 + 2; \"unused\"; print \"a\" + \"b\"; print nil;").unwrap();
+        assert_eq!(output, "3\nab\nnil\n");
+    }
+
+    #[test]
+    fn test_runtime_error_stops_execution() {
+        init_logger();
+        let result = run("print 1; print -nil; print 2;");
+        assert!(matches!(result, Err(RuntimeError::OperandMustBeNumber(_))));
+    }
+
+    #[test]
+    fn test_variables() {
+        init_logger();
+        let output =
+            run("var a = 1; var b; print a; print b; b = a = a + 1; print a + b;").unwrap();
+        assert_eq!(output, "1\nnil\n4\n");
+    }
+
+    #[test]
+    fn test_redeclaration_replaces_global() {
+        init_logger();
+        let output = run("var a = \"first\"; var a = \"second\"; print a;").unwrap();
+        assert_eq!(output, "second\n");
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        init_logger();
+        assert!(matches!(run("print missing;"), Err(RuntimeError::UndefinedVariable(_))));
+        assert!(matches!(run("missing = 1;"), Err(RuntimeError::UndefinedVariable(_))));
+    }
+
+    #[test]
+    fn test_nested_scopes_and_shadowing() {
+        init_logger();
+        let output = run("
+            var a = \"global a\";
+            var b = \"global b\";
+            var c = \"global c\";
+            {
+                var a = \"outer a\";
+                var b = \"outer b\";
+                {
+                    var a = \"inner a\";
+                    print a;
+                    print b;
+                    print c;
+                }
+                print a;
+                print b;
+                print c;
+            }
+            print a;
+            print b;
+            print c;
+        ")
+        .unwrap();
+        assert_eq!(
+            output,
+            "inner a\nouter b\nglobal c\nouter a\nouter b\nglobal c\nglobal a\nglobal b\nglobal c\n"
+        );
+    }
+
+    #[test]
+    fn test_assignment_in_block_updates_enclosing_scope() {
+        init_logger();
+        let output = run("var a = 1; { a = 2; var a = 3; a = 4; print a; } print a;").unwrap();
+        assert_eq!(output, "4\n2\n");
+    }
+
+    #[test]
+    fn test_scope_restored_after_error() {
+        init_logger();
+        let interpreter = Interpreter::with_output(CapturedOutput::default());
+        let tokens = crate::scanner::Scanner::new("var a = 1; { var a = 2; -nil; }");
+        let statements =
+            Parser::new(tokens, &mut interpreter.interner()).parse_program().into_result().unwrap();
+        assert!(interpreter.interpret(&statements).is_err());
+        let tokens = crate::scanner::Scanner::new("a");
+        let expression = Parser::new(tokens, &mut interpreter.interner()).parse().unwrap();
+        assert_eq!(interpreter.evaluate(&expression).unwrap(), LoxValue::Number(1.0));
+    }
+
+    #[test]
+    fn test_if_else() {
+        init_logger();
+        let output = run("if (1 < 2) print \"then\"; else print \"else\";
+                          if (nil) print \"then\"; else print \"else\";
+                          if (false) print \"unreachable\";")
+        .unwrap();
+        assert_eq!(output, "then\nelse\n");
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        init_logger();
+        let output = run("print \"hi\" or 2; print nil or \"yes\"; print nil and missing;
+                          print 1 and 2; var a = false; true or (a = true); print a;")
+        .unwrap();
+        assert_eq!(output, "hi\nyes\nnil\n2\nfalse\n");
+    }
+
+    #[test]
+    fn test_while_and_for() {
+        init_logger();
+        let output = run("
+            var i = 0;
+            while (i < 3) { print i; i = i + 1; }
+            var a = 0;
+            var temp;
+            for (var b = 1; a < 20; b = temp + b) { print a; temp = a; a = b; }
+        ")
+        .unwrap();
+        assert_eq!(output, "0\n1\n2\n0\n1\n1\n2\n3\n5\n8\n13\n");
+    }
+
+    #[test]
+    fn test_for_loop_variable_is_scoped() {
+        init_logger();
+        let result = run("for (var i = 0; i < 1; i = i + 1) {} print i;");
+        assert!(matches!(result, Err(RuntimeError::UndefinedVariable(_))));
+    }
+
+    #[test]
+    fn test_functions() {
+        init_logger();
+        let output = run("
+            fun sayHi(first, last) { print \"Hi, \" + first + \" \" + last + \"!\"; }
+            sayHi(\"Dear\", \"Reader\");
+            fun fib(n) { if (n <= 1) return n; return fib(n - 2) + fib(n - 1); }
+            print fib(10);
+            fun noReturn() {}
+            print noReturn();
+            print fib;
+            print clock;
+        ")
+        .unwrap();
+        assert_eq!(output, "Hi, Dear Reader!\n55\nnil\n<fn fib>\n<native fn>\n");
+    }
+
+    #[test]
+    fn test_closures() {
+        init_logger();
+        let output = run("
+            fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var first = makeCounter();
+            var second = makeCounter();
+            print first();
+            print first();
+            print second();
+
+            fun apply(f, x) { return f(x); }
+            fun twice(x) { return x * 2; }
+            print apply(twice, 21);
+        ")
+        .unwrap();
+        assert_eq!(output, "1\n2\n1\n42\n");
+    }
+
+    #[test]
+    fn test_native_functions() {
+        init_logger();
+        let output = run("var t = clock(); print t > 0; print clock() >= t;").unwrap();
+        assert_eq!(output, "true\ntrue\n");
+
+        let output = CapturedOutput::default();
+        let interpreter = Interpreter::with_output(output.clone());
+        interpreter.define_native("double", 1, |_, arguments| match arguments {
+            [LoxValue::Number(n)] => Ok(LoxValue::Number(n * 2.0)),
+            _ => Ok(LoxValue::Nil),
+        });
+        let tokens = crate::scanner::Scanner::new("print double(4);");
+        let statements =
+            Parser::new(tokens, &mut interpreter.interner()).parse_program().into_result().unwrap();
+        interpreter.interpret(&statements).unwrap();
+        assert_eq!(output.contents(), "8\n");
+    }
+
+    #[test]
+    fn test_closures_capture_declaration_scope() {
+        init_logger();
+        let output = run("
+            var a = \"global\";
+            {
+                fun showA() { print a; }
+                showA();
+                var a = \"block\";
+                showA();
+                print a;
+            }
+        ")
+        .unwrap();
+        assert_eq!(output, "global\nglobal\nblock\n");
+    }
+
+    #[test]
+    fn test_resolution_survives_between_runs() {
+        init_logger();
+        let output = CapturedOutput::default();
+        let interpreter = Interpreter::with_output(output.clone());
+        run_with(&interpreter, "fun outer() { var x = 1; fun get() { return x; } return get; }")
+            .unwrap();
+        run_with(&interpreter, "var get = outer(); { var x = 2; print get(); }").unwrap();
+        assert_eq!(output.contents(), "1\n");
+    }
+
+    #[test]
+    fn test_runs_do_not_accumulate_locals() {
+        init_logger();
+        let interpreter = Interpreter::with_output(CapturedOutput::default());
+        run_with(&interpreter, "fun f() { var a = 1; return a; }").unwrap();
+        let declared = Rc::downgrade(&interpreter.locals());
+        for _ in 0..3 {
+            run_with(&interpreter, "{ var b = f(); print b; }").unwrap();
+            assert_eq!(interpreter.locals().len(), 1);
+        }
+        // `f` still needs the table it was resolved with.
+        assert!(declared.upgrade().is_some());
+        run_with(&interpreter, "f = nil;").unwrap();
+        assert!(declared.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_classes() {
+        init_logger();
+        let output = run("
+            class Bagel {}
+            var bagel = Bagel();
+            print Bagel;
+            print bagel;
+            bagel.topping = \"cream cheese\";
+            print bagel.topping;
+
+            class Counter {
+                init(start) { this.count = start; }
+                increment() { this.count = this.count + 1; return this; }
+            }
+            var counter = Counter(10);
+            print counter.increment().increment().count;
+            var increment = counter.increment;
+            increment();
+            print counter.count;
+        ")
+        .unwrap();
+        assert_eq!(output, "Bagel\nBagel instance\ncream cheese\n12\n13\n");
+    }
+
+    #[test]
+    fn test_initializer_returns_this() {
+        init_logger();
+        let output = run("
+            class Foo {
+                init() { this.ready = true; return; }
+            }
+            var foo = Foo();
+            print foo.init() == foo;
+            print foo.ready;
+        ")
+        .unwrap();
+        assert_eq!(output, "true\ntrue\n");
+    }
+
+    #[test]
+    fn test_inheritance() {
+        init_logger();
+        let output = run("
+            class Doughnut {
+                cook() { print \"Fry until golden brown.\"; }
+                name() { return \"doughnut\"; }
+            }
+            class BostonCream < Doughnut {
+                cook() {
+                    super.cook();
+                    print \"Pipe full of custard and coat with chocolate.\";
+                }
+            }
+            var pastry = BostonCream();
+            pastry.cook();
+            print pastry.name();
+        ")
+        .unwrap();
+        assert_eq!(
+            output,
+            "Fry until golden brown.\nPipe full of custard and coat with chocolate.\ndoughnut\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_shadow_methods() {
+        init_logger();
+        let output = run("
+            class Box { value() { return \"method\"; } }
+            var box = Box();
+            fun field() { return \"field\"; }
+            box.value = field;
+            print box.value();
+        ")
+        .unwrap();
+        assert_eq!(output, "field\n");
+    }
+
+    #[test]
+    fn test_class_errors() {
+        init_logger();
+        assert!(matches!(
+            run("var a = 1; print a.b;"),
+            Err(RuntimeError::OnlyInstancesHaveProperties(_))
+        ));
+        assert!(matches!(
+            run("var a = 1; a.b = 2;"),
+            Err(RuntimeError::OnlyInstancesHaveFields(_))
+        ));
+        assert!(matches!(
+            run("class A {} print A().missing;"),
+            Err(RuntimeError::UndefinedProperty(_))
+        ));
+        assert!(matches!(
+            run("var B = 1; class A < B {}"),
+            Err(RuntimeError::SuperclassMustBeClass(_))
+        ));
+        assert!(matches!(
+            run("class A { init(a) {} } A();"),
+            Err(RuntimeError::ArityMismatch {
+                expected: 1,
+                found: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_call_errors() {
+        init_logger();
+        assert!(matches!(run("\"not a function\"();"), Err(RuntimeError::NotCallable(_))));
+        assert!(matches!(
+            run("fun f(a, b) {} f(1);"),
+            Err(RuntimeError::ArityMismatch {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        init_logger();
+        let error = with_stack(|| run("fun f() { f(); }\nf();").unwrap_err().to_string());
+        assert_eq!(error, "Stack overflow.\n[line 1]");
+
+        // The depth is back to zero afterwards, and recursion that ends in
+        // time is fine, through methods and initializers too.
+        let output = with_stack(|| {
+            let output = CapturedOutput::default();
+            let interpreter = Interpreter::with_output(output.clone());
+            assert!(run_with(&interpreter, "class A { init() { A(); } } A();").is_err());
+            run_with(
+                &interpreter,
+                "fun count(n) { if (n > 0) { while (true) return count(n - 1) + 1; } return 0; }
+                print count(200);",
+            )
+            .unwrap();
+            output.contents()
+        });
+        assert_eq!(output, "200\n");
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        init_logger();
+        // As deep as jlox goes with the JVM's default stack, and then some.
+        let output = with_stack(|| {
+            run("
+                class Node { init(next) { this.next = next; } }
+                fun build(n) { if (n == 0) return nil; return Node(build(n - 1)); }
+                fun length(list) { if (list == nil) return 0; return 1 + length(list.next); }
+                print length(build(1000));
+                fun count(n) { if (n > 0) { while (true) return count(n - 1) + 1; } return 0; }
+                print count(5000);
+            ")
+            .unwrap()
+        });
+        assert_eq!(output, "1000\n5000\n");
+    }
+
+    #[test]
+    fn test_type_errors() {
+        init_logger();
+        assert!(matches!(evaluate("-\"a\""), Err(RuntimeError::OperandMustBeNumber(_))));
+        assert!(matches!(evaluate("1 < \"a\""), Err(RuntimeError::OperandsMustBeNumbers(_))));
+        assert!(matches!(
+            evaluate("1 + \"a\""),
+            Err(RuntimeError::OperandsMustBeNumbersOrStrings(_))
+        ));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        init_logger();
+        let output = run(r#"
+            var name = "Lox";
+            var age = 29;
+            class Pie {}
+            print "Hello ${name}, you are ${age + 1}!";
+            print "${nil} ${true} ${1.5} ${Pie} ${"in${"ner"}"}";
+        "#)
+        .unwrap();
+        assert_eq!(output, "Hello Lox, you are 30!\nnil true 1.5 Pie inner\n");
+    }
+
+    #[test]
+    fn test_conditional_and_comma() {
+        init_logger();
+        let output = run(r#"
+            print true ? "yes" : "no";
+            print nil ? 1 : false ? 2 : 3;
+            print false ? undefined() : "skipped";
+            var i = 0;
+            var last = (i = i + 1, i = i + 10, i);
+            print last;
+        "#)
+        .unwrap();
+        assert_eq!(output, "yes\n3\nskipped\n11\n");
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn test_normalized_names_are_equal() {
+        init_logger();
+        let output = run("var cafe\u{301} = \"open\"; print caf\u{e9};").unwrap();
+        assert_eq!(output, "open\n");
+    }
+}
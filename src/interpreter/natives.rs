@@ -0,0 +1,36 @@
+use crate::interpreter::{Interpreter, RuntimeError, callable::NativeFunction, value::LoxValue};
+
+/// Native functions every interpreter starts with.
+pub const NATIVES: &[NativeFunction] = &[NativeFunction {
+    name: "clock",
+    arity: 0,
+    function: clock,
+}];
+
+/// Seconds since the Unix epoch, with sub-second precision.
+fn clock(_: &Interpreter, _: &[LoxValue]) -> Result<LoxValue, RuntimeError> {
+    Ok(LoxValue::Number(now_millis() / 1000.0))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_millis() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64() * 1000.0)
+        .unwrap_or_default()
+}
+
+/// `SystemTime` is unavailable on `wasm32-unknown-unknown`, so the playground
+/// asks the browser instead.
+#[cfg(target_arch = "wasm32")]
+fn now_millis() -> f64 {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date)]
+        fn now() -> f64;
+    }
+
+    now()
+}
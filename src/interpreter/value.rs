@@ -0,0 +1,90 @@
+use std::{
+    cell::RefCell,
+    fmt::{Display, Formatter},
+    rc::Rc,
+};
+
+use crate::interpreter::{
+    callable::{LoxCallable, LoxFunction, NativeFunction},
+    class::{LoxClass, LoxInstance},
+};
+
+/// A runtime value produced by evaluating Lox code.
+#[derive(Debug, Clone)]
+pub enum LoxValue {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+impl LoxValue {
+    /// Lox follows Ruby's rule: `false` and `nil` are falsey, everything else
+    /// is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, LoxValue::Nil | LoxValue::Boolean(false))
+    }
+
+    pub fn as_callable(&self) -> Option<&dyn LoxCallable> {
+        match self {
+            LoxValue::Function(function) => Some(function.as_ref()),
+            LoxValue::NativeFunction(function) => Some(function.as_ref()),
+            LoxValue::Class(class) => Some(class),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoxFunction> for LoxValue {
+    fn from(function: LoxFunction) -> Self {
+        LoxValue::Function(Rc::new(function))
+    }
+}
+
+impl From<LoxClass> for LoxValue {
+    fn from(class: LoxClass) -> Self {
+        LoxValue::Class(Rc::new(class))
+    }
+}
+
+impl From<NativeFunction> for LoxValue {
+    fn from(function: NativeFunction) -> Self {
+        LoxValue::NativeFunction(Rc::new(function))
+    }
+}
+
+/// Primitives compare by value, everything else by identity.
+impl PartialEq for LoxValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxValue::Nil, LoxValue::Nil) => true,
+            (LoxValue::Boolean(l), LoxValue::Boolean(r)) => l == r,
+            (LoxValue::Number(l), LoxValue::Number(r)) => l == r,
+            (LoxValue::String(l), LoxValue::String(r)) => l == r,
+            (LoxValue::Function(l), LoxValue::Function(r)) => Rc::ptr_eq(l, r),
+            (LoxValue::NativeFunction(l), LoxValue::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (LoxValue::Class(l), LoxValue::Class(r)) => Rc::ptr_eq(l, r),
+            (LoxValue::Instance(l), LoxValue::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl Display for LoxValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxValue::Nil => write!(f, "nil"),
+            LoxValue::Boolean(b) => write!(f, "{}", b),
+            LoxValue::Number(n) => write!(f, "{}", n),
+            LoxValue::String(s) => write!(f, "{}", s),
+            LoxValue::Function(function) => write!(f, "{}", function),
+            LoxValue::NativeFunction(function) => write!(f, "{}", function),
+            LoxValue::Class(class) => write!(f, "{}", class),
+            LoxValue::Instance(instance) => write!(f, "{}", instance.borrow()),
+        }
+    }
+}
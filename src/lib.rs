@@ -1,39 +1,105 @@
-use std::ops::Not;
+use std::io::IsTerminal;
 
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+pub mod diagnostic;
+pub mod interner;
+pub mod interpreter;
 pub mod parser;
+pub mod resolver;
 pub mod scanner;
+pub mod span;
+
+thread_local! {
+    /// The playground REPL keeps one interpreter alive so that variables
+    /// survive between lines.
+    static REPL: (interpreter::Interpreter, interpreter::CapturedOutput) = {
+        let output = interpreter::CapturedOutput::default();
+        (interpreter::Interpreter::with_output(output.clone()), output)
+    };
+}
 
 #[wasm_bindgen]
 pub fn parse_to_ast(input: String) -> JsValue {
-    let tokens = scanner::scan(&input).collect();
-    let mut parser = parser::Parser::new(tokens);
-    match parser.parse() {
-        Ok(ast) => serde_wasm_bindgen::to_value(&ast).unwrap(),
-        Err(e) => JsValue::from_str(&format!("Parser error: {}", e)),
+    let mut interner = interner::Interner::new();
+    match parse(&input, &mut interner, diagnostic::Renderer::default()) {
+        Ok(ast) => parser::json::to_json(&ast, &interner)
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .unwrap(),
+        Err(errors) => JsValue::from_str(&format!("Parser error:\n{}", errors)),
+    }
+}
+
+/// The syntax tree of `input` as a Graphviz digraph, or the rendered syntax
+/// errors.
+#[wasm_bindgen]
+pub fn ast_to_dot(input: String) -> String {
+    let mut interner = interner::Interner::new();
+    match parse(&input, &mut interner, diagnostic::Renderer::default()) {
+        Ok(ast) => parser::dot::DotPrinter::new(&interner).print_program(&ast),
+        Err(errors) => format!("Parser error:\n{}", errors),
+    }
+}
+
+/// The syntax tree of `input` as S-expressions, or the rendered syntax
+/// errors.
+#[wasm_bindgen]
+pub fn ast_to_sexpr(input: String) -> String {
+    let mut interner = interner::Interner::new();
+    match parse(&input, &mut interner, diagnostic::Renderer::default()) {
+        Ok(ast) => parser::sexpr::SexprPrinter::new(&interner).print_program(&ast),
+        Err(errors) => format!("Parser error:\n{}", errors),
     }
 }
 
+/// Parses `source` as a program, interning its names and strings with
+/// `interner`. Syntax errors are rendered as diagnostics.
+pub fn parse(
+    source: &str,
+    interner: &mut interner::Interner,
+    renderer: diagnostic::Renderer,
+) -> Result<Vec<parser::ast::Statement>, String> {
+    parser::Parser::new(scanner::Scanner::new(source), interner)
+        .parse_program()
+        .into_result()
+        .map_err(|errors| render(&errors, &span::SourceMap::new(source), renderer))
+}
+
 pub fn run(input: String, print_tokens: bool) {
-    let tokens = scanner::scan(&input).collect();
+    run_with(&interpreter::Interpreter::new(), input, print_tokens);
+}
+
+/// Runs `input` with an existing interpreter so that global state is kept
+/// between calls, as the REPL needs.
+pub fn run_with(interpreter: &interpreter::Interpreter, input: String, print_tokens: bool) {
     if print_tokens {
-        println!("{}", scanner::pretty(&tokens));
+        println!("{}", scanner::pretty(&scanner::scan(&input).collect::<Vec<_>>()));
+    }
+    let renderer = diagnostic::Renderer::new(std::io::stderr().is_terminal());
+    if let Err(e) = execute(&input, interpreter, renderer) {
+        eprintln!("{}", e);
+    }
+}
+
+/// Runs a program given as a document in the JSON AST format of
+/// [`parser::json`], as produced by `rlox ast --format json`.
+pub fn run_ast(document: &str) {
+    if let Err(e) = execute_ast(document, &interpreter::Interpreter::new()) {
+        eprintln!("{}", e);
     }
 }
 
 #[wasm_bindgen]
 pub fn run_lox(input: String) -> String {
-    // For now, just return the pretty-printed tokens
-    let scanner = scanner::Scanner::scan_string(input);
-
     let mut output = String::new();
-    if scanner.get_errors().is_empty().not() {
-        scanner.get_errors().iter().for_each(|error| {
-            output.push_str(&format!("{}\n", error));
-        });
-    }
-    output.push_str(&format!("{}\n", scanner::pretty(&scanner.get_tokens())));
+    REPL.with(|(interpreter, captured)| {
+        let result = execute(&input, interpreter, diagnostic::Renderer::default());
+        output.push_str(&captured.take());
+        if let Err(e) = result {
+            output.push_str(&format!("{}\n", e));
+        }
+    });
     output
 }
 
@@ -42,3 +108,187 @@ pub fn tokenize(input: String) -> JsValue {
     let tokens: Vec<scanner::Token> = scanner::scan(&input).collect();
     serde_wasm_bindgen::to_value(&tokens).unwrap()
 }
+
+/// Scans the playground editor as it changes, relexing only around each
+/// edit instead of the whole buffer.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Tokenizer {
+    source: String,
+    tokens: scanner::TokenStore,
+    /// An offset into `source` in UTF-16 code units and in bytes, next to
+    /// the latest edit, so that converting between them for the next one
+    /// only walks the text in between.
+    cursor: (usize, usize),
+}
+
+/// What an edit did to the tokens, for the editor to patch its own copy of
+/// them with.
+#[derive(Serialize)]
+struct TokenEdit<'src> {
+    start: usize,
+    end: usize,
+    tokens: Vec<EditorToken<'src>>,
+    shift: scanner::Shift,
+}
+
+#[derive(Serialize)]
+struct EditorToken<'src> {
+    #[serde(flatten)]
+    token: scanner::KeptToken,
+    lexeme: &'src str,
+}
+
+#[wasm_bindgen]
+impl Tokenizer {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Tokenizer {
+        Tokenizer::default()
+    }
+
+    /// Applies an edit in the editor replacing the text from `start` to
+    /// `end` with `text`, counted in UTF-16 code units as JavaScript strings
+    /// are. Returns the indices of the tokens it replaced, the tokens that
+    /// took their place, and how the tokens after them moved.
+    pub fn update(&mut self, start: usize, end: usize, text: String) -> JsValue {
+        let span = span::Span::new(self.byte_offset(start), self.byte_offset(end));
+        self.cursor = (start + text.encode_utf16().count(), span.start + text.len());
+        let edit = span::TextEdit::new(span, text);
+        self.source.replace_range(span.start..span.end, &edit.text);
+        let relexed = scanner::Scanner::relex(&self.source, &mut self.tokens, &edit);
+        let tokens = relexed
+            .inserted
+            .filter_map(|index| self.tokens.get(index))
+            .map(|token| EditorToken {
+                lexeme: &self.source[token.span.start..token.span.end],
+                token,
+            })
+            .collect();
+        let edit = TokenEdit {
+            start: relexed.replaced.start,
+            end: relexed.replaced.end,
+            tokens,
+            shift: relexed.shift,
+        };
+        serde_wasm_bindgen::to_value(&edit).unwrap()
+    }
+
+    /// The byte offset into the source of the UTF-16 offset `units`.
+    fn byte_offset(&self, units: usize) -> usize {
+        let (mut position, mut offset) = self.cursor;
+        while position < units
+            && let Some(c) = self.source[offset..].chars().next()
+        {
+            position += c.len_utf16();
+            offset += c.len_utf8();
+        }
+        while position > units
+            && let Some(c) = self.source[..offset].chars().next_back()
+        {
+            position -= c.len_utf16();
+            offset -= c.len_utf8();
+        }
+        offset
+    }
+}
+
+/// Parses, resolves and runs `source` as a program with `interpreter`.
+/// Errors found before running are rendered as diagnostics.
+fn execute(
+    source: &str,
+    interpreter: &interpreter::Interpreter,
+    renderer: diagnostic::Renderer,
+) -> Result<(), String> {
+    let statements = parse(source, &mut interpreter.interner(), renderer)?;
+    let locals = resolver::Resolver::new()
+        .resolve(&statements)
+        .map_err(|errors| render(&errors, &span::SourceMap::new(source), renderer))?;
+    interpreter.resolve(locals);
+    interpreter.interpret(&statements).map_err(|e| e.to_string())
+}
+
+/// Like [`execute`], for a program loaded from JSON. There is no source to
+/// show errors against, so they are reported by line.
+fn execute_ast(document: &str, interpreter: &interpreter::Interpreter) -> Result<(), String> {
+    let statements =
+        parser::json::load(document, &mut interpreter.interner()).map_err(|e| e.to_string())?;
+    let locals = resolver::Resolver::new()
+        .resolve(&statements)
+        .map_err(|errors| errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))?;
+    interpreter.resolve(locals);
+    interpreter.interpret(&statements).map_err(|e| e.to_string())
+}
+
+fn render<E>(errors: &[E], source_map: &span::SourceMap, renderer: diagnostic::Renderer) -> String
+where
+    for<'e> &'e E: Into<diagnostic::Diagnostic>,
+{
+    let diagnostics: Vec<_> = errors.iter().map(Into::into).collect();
+    renderer.render_all(&diagnostics, source_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter::Trace;
+
+    use super::*;
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
+    }
+
+    /// Runs `source` both directly and through the JSON AST format,
+    /// returning the output and error of each.
+    fn run_both_ways(source: &str) -> [(String, Option<String>); 2] {
+        let mut interner = interner::Interner::new();
+        let statements = parse(source, &mut interner, Default::default()).unwrap();
+        let document = parser::json::to_json(&statements, &interner);
+        let run = |execute: &dyn Fn(&interpreter::Interpreter) -> Result<(), String>| {
+            let output = interpreter::CapturedOutput::default();
+            let error = execute(&interpreter::Interpreter::with_output(output.clone())).err();
+            (output.contents(), error)
+        };
+        [
+            run(&|interpreter| execute(source, interpreter, Default::default())),
+            run(&|interpreter| execute_ast(&document.to_string(), interpreter)),
+        ]
+    }
+
+    #[test]
+    fn test_run_ast() {
+        init_logger();
+        let source = "fun add(a, b) { return a + b; }\nprint add(1, 2);\n\nprint add(1, nil);";
+        let [direct, loaded] = run_both_ways(source);
+        assert_eq!(loaded, direct);
+        assert_eq!(
+            loaded,
+            (
+                "3\n".to_string(),
+                Some("Operands must be two numbers or two strings.\n[line 1]".into())
+            )
+        );
+
+        let [_, (_, error)] = run_both_ways("{ var a = 1;\n  var a = 2; }");
+        assert_eq!(
+            error.unwrap(),
+            "[line 2] Error at 'a': Already a variable with this name in this scope."
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_offsets() {
+        let mut tokenizer = Tokenizer {
+            source: "aé😀b".to_string(),
+            ..Tokenizer::default()
+        };
+        // The emoji is two UTF-16 code units and four bytes.
+        let offsets = [(0, 0), (1, 1), (2, 3), (4, 7), (5, 8)];
+        for (units, bytes) in offsets {
+            assert_eq!(tokenizer.byte_offset(units), bytes);
+        }
+        tokenizer.cursor = (5, 8);
+        for (units, bytes) in offsets.into_iter().rev() {
+            assert_eq!(tokenizer.byte_offset(units), bytes);
+        }
+    }
+}
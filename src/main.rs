@@ -1,33 +1,99 @@
 use std::{
-    io::Write,
+    io::{IsTerminal, Write},
     path::{Path, PathBuf},
 };
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use log::{error, info};
-use rlox;
+use rlox::{
+    interner::Interner,
+    parser::{ast::PrettyPrinter, dot::DotPrinter, json, sexpr::SexprPrinter},
+};
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct RloxArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to the script file to execute
     script: Option<String>,
 
     /// Print tokens during execution
-    #[arg(short, long, default_value_t = true)]
+    #[arg(short, long)]
     print_tokens: bool,
 }
 
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run a script, or a syntax tree printed by `rlox ast --format json`
+    Run {
+        /// Path to the script file to execute
+        #[arg(required_unless_present = "ast")]
+        script: Option<String>,
+
+        /// Path to a JSON syntax tree to execute instead of a script
+        #[arg(long, value_name = "FILE", conflicts_with = "script")]
+        ast: Option<PathBuf>,
+    },
+    /// Print the syntax tree of a script
+    Ast {
+        /// Path to the script file to parse, or standard input if omitted
+        script: Option<String>,
+
+        /// How to print the tree
+        #[arg(short, long, value_enum, default_value_t = AstFormat::Pretty)]
+        format: AstFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AstFormat {
+    /// Lox source, with every operation in parentheses
+    Pretty,
+    /// The versioned JSON format read back by `rlox::parser::json`
+    Json,
+    /// A Graphviz digraph, for `dot -Tsvg`
+    Dot,
+    /// Lisp-style S-expressions, one per statement
+    Sexpr,
+}
+
 fn main() {
     env_logger::builder().filter_level(log::LevelFilter::Trace).init();
 
     let args = RloxArgs::parse();
-    if let Some(script) = args.script {
-        info!("Executing script: {}", script);
-        run_file(PathBuf::from(&script).as_path(), args.print_tokens);
-    } else {
-        info!("Run in interactive mode.");
-        repl();
-    }
+    // Lox code recurses on the Rust stack, which the main thread has too
+    // little of.
+    rlox::interpreter::with_stack(|| match (args.command, args.script) {
+        (
+            Some(Command::Ast {
+                script,
+                format,
+            }),
+            _,
+        ) => print_ast(script.as_deref(), format),
+        (
+            Some(Command::Run {
+                ast: Some(path), ..
+            }),
+            _,
+        ) => run_ast(&path),
+        (
+            Some(Command::Run {
+                script: Some(script), ..
+            }),
+            _,
+        )
+        | (None, Some(script)) => {
+            info!("Executing script: {}", script);
+            run_file(PathBuf::from(&script).as_path(), args.print_tokens);
+        },
+        _ => {
+            info!("Run in interactive mode.");
+            repl();
+        },
+    });
 }
 
 fn run_file(path: &Path, print_tokens: bool) {
@@ -37,12 +103,57 @@ fn run_file(path: &Path, print_tokens: bool) {
     }
 }
 
+fn run_ast(path: &Path) {
+    info!("Executing syntax tree: {}", path.display());
+    match std::fs::read_to_string(path) {
+        Ok(document) => rlox::run_ast(&document),
+        Err(e) => error!("Failed to read file: {}", e),
+    }
+}
+
+/// Prints the syntax tree of `script` to standard output. Exits with status
+/// 65 if it does not parse and 66 if it cannot be read, as in `sysexits.h`.
+fn print_ast(script: Option<&str>, format: AstFormat) {
+    let source = match script {
+        Some(path) => std::fs::read_to_string(path),
+        None => std::io::read_to_string(std::io::stdin()),
+    };
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => {
+            error!("Failed to read script: {}", e);
+            std::process::exit(66);
+        },
+    };
+    let renderer = rlox::diagnostic::Renderer::new(std::io::stderr().is_terminal());
+    let mut interner = Interner::new();
+    let statements = match rlox::parse(&source, &mut interner, renderer) {
+        Ok(statements) => statements,
+        Err(errors) => {
+            eprintln!("{}", errors);
+            std::process::exit(65);
+        },
+    };
+    match format {
+        AstFormat::Pretty => {
+            println!("{}", PrettyPrinter::clear(&interner).print_program(&statements))
+        },
+        AstFormat::Json => {
+            let document = json::to_json(&statements, &interner);
+            println!("{}", serde_json::to_string_pretty(&document).unwrap());
+        },
+        AstFormat::Dot => println!("{}", DotPrinter::new(&interner).print_program(&statements)),
+        AstFormat::Sexpr => println!("{}", SexprPrinter::new(&interner).print_program(&statements)),
+    }
+}
+
 fn repl() {
+    let interpreter = rlox::interpreter::Interpreter::new();
     loop {
         let mut input = String::new();
         print!("> ");
         std::io::stdout().flush().unwrap();
         std::io::stdin().read_line(&mut input).unwrap();
-        rlox::run(input, false);
+        rlox::run_with(&interpreter, input, false);
     }
 }
@@ -1,51 +1,316 @@
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 use crate::{
+    interner::Interner,
     lox_ast,
-    scanner::{LiteralValue, Token},
+    scanner::{LiteralValue, OwnedToken, TokenType},
+    span::{MaybeSpanned, Span},
 };
 
 lox_ast!(
-    Expression {
+    Expression: Expr {
+        Assign(
+            id: NodeId,
+            name: OwnedToken,
+            value: Box<Expression>,
+        ),
         Binary(
             left: Box<Expression>,
-            operator: Token,
+            operator: OwnedToken,
             right: Box<Expression>,
         ),
+        Call(
+            callee: Box<Expression>,
+            paren: OwnedToken,
+            arguments: Vec<Expression>,
+        ),
+        Conditional(
+            condition: Box<Expression>,
+            then_branch: Box<Expression>,
+            else_branch: Box<Expression>,
+        ),
+        Get(
+            object: Box<Expression>,
+            name: OwnedToken,
+        ),
         Grouping(
             expression: Box<Expression>,
+            /// Includes the parentheses, which have no token of their own.
+            span: Span,
+        ),
+        Interpolation(
+            /// The `StringStart`, `StringMiddle` and `StringEnd` tokens
+            /// around `expressions`, one more than there are expressions.
+            strings: Vec<OwnedToken>,
+            expressions: Vec<Expression>,
         ),
         Literal(
             value: LiteralValue,
+            span: Span,
+        ),
+        Logical(
+            left: Box<Expression>,
+            operator: OwnedToken,
+            right: Box<Expression>,
+        ),
+        Set(
+            object: Box<Expression>,
+            name: OwnedToken,
+            value: Box<Expression>,
+        ),
+        Super as super_(
+            id: NodeId,
+            keyword: OwnedToken,
+            method: OwnedToken,
+        ),
+        This(
+            id: NodeId,
+            keyword: OwnedToken,
         ),
         Unary(
-            operator: Token,
+            operator: OwnedToken,
             right: Box<Expression>,
         ),
+        Variable(
+            id: NodeId,
+            name: OwnedToken,
+        ),
     }
 );
 
-#[derive(Default)]
-pub struct PrettyPrinter {
+lox_ast!(
+    Statement: Stmt {
+        Block(
+            statements: Vec<Statement>,
+            /// Includes the braces.
+            span: Span,
+        ),
+        Class(
+            name: OwnedToken,
+            superclass: Option<VariableExpr>,
+            methods: Vec<FunctionStmt>,
+            /// From `class` through the closing brace.
+            span: Span,
+        ),
+        Expression(
+            expression: Expression,
+            /// Includes the `;`.
+            span: Span,
+        ),
+        Function(
+            name: OwnedToken,
+            params: Vec<OwnedToken>,
+            body: Rc<Vec<Statement>>,
+            /// From `fun`, or the name of a method, through the closing
+            /// brace.
+            span: Span,
+        ),
+        If as if_(
+            condition: Expression,
+            then_branch: Box<Statement>,
+            else_branch: Option<Box<Statement>>,
+            /// From `if` through the end of the last branch.
+            span: Span,
+        ),
+        Print(
+            expression: Expression,
+            /// From `print` through the `;`.
+            span: Span,
+        ),
+        Return as return_(
+            keyword: OwnedToken,
+            value: Option<Expression>,
+            /// From `return` through the `;`.
+            span: Span,
+        ),
+        Var(
+            name: OwnedToken,
+            initializer: Option<Expression>,
+            /// From `var` through the `;`.
+            span: Span,
+        ),
+        While as while_(
+            condition: Expression,
+            body: Box<Statement>,
+            /// From `while`, or `for` for a desugared loop, through the end of
+            /// the body.
+            span: Span,
+        ),
+    }
+);
+
+/// Identifies an expression that refers to a variable, so later passes can
+/// attach information to it in a side table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(pub usize);
+
+impl NodeId {
+    /// Returns an id that is unique for the lifetime of the process, so that
+    /// ASTs parsed separately (such as REPL lines) never collide.
+    pub fn fresh() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl MaybeSpanned for NodeId {
+    fn maybe_span(&self) -> Option<Span> {
+        None
+    }
+}
+
+pub struct PrettyPrinter<'a> {
+    /// Resolves string literals, which are interned by the parser.
+    interner: &'a Interner,
     /// Whether to use parentheses to clearly show order of operations.
     clear: bool,
 }
 
-impl PrettyPrinter {
-    pub fn clear() -> Self {
+impl<'a> PrettyPrinter<'a> {
+    pub fn new(interner: &'a Interner) -> Self {
         PrettyPrinter {
+            interner,
+            clear: false,
+        }
+    }
+
+    pub fn clear(interner: &'a Interner) -> Self {
+        PrettyPrinter {
+            interner,
             clear: true,
         }
     }
+
+    pub fn print_program(&self, statements: &[Statement]) -> String {
+        let lines = statements.iter().filter_map(|s| s.accept(self).ok()).collect::<Vec<_>>();
+        lines.join("\n")
+    }
+
+    /// A function or method declaration without the leading `fun`.
+    fn function(&self, stmt: &FunctionStmt) -> Result<String, ()> {
+        let params = stmt.params.iter().map(|p| p.lexeme.as_str()).collect::<Vec<_>>();
+        let body = self.block(&stmt.body)?;
+        Ok(format!("{}({}) {}", stmt.name.lexeme, params.join(", "), body))
+    }
+
+    fn block(&self, statements: &[Statement]) -> Result<String, ()> {
+        if statements.is_empty() {
+            return Ok("{}".to_string());
+        }
+        let statements =
+            statements.iter().map(|s| s.accept(self)).collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{{ {} }}", statements.join(" ")))
+    }
 }
 
-impl ExpressionVisitor<String, ()> for PrettyPrinter {
+impl StatementVisitor<String, ()> for PrettyPrinter<'_> {
+    fn visit_block(&self, stmt: &BlockStmt) -> Result<String, ()> {
+        self.block(&stmt.statements)
+    }
+
+    fn visit_class(&self, stmt: &ClassStmt) -> Result<String, ()> {
+        let mut header = format!("class {}", stmt.name.lexeme);
+        if let Some(superclass) = &stmt.superclass {
+            header.push_str(&format!(" < {}", superclass.name.lexeme));
+        }
+        if stmt.methods.is_empty() {
+            return Ok(format!("{} {{}}", header));
+        }
+        let methods =
+            stmt.methods.iter().map(|m| self.function(m)).collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{} {{ {} }}", header, methods.join(" ")))
+    }
+
+    fn visit_expression(&self, stmt: &ExpressionStmt) -> Result<String, ()> {
+        Ok(format!("{};", stmt.expression.accept(self)?))
+    }
+
+    fn visit_function(&self, stmt: &FunctionStmt) -> Result<String, ()> {
+        Ok(format!("fun {}", self.function(stmt)?))
+    }
+
+    fn visit_if(&self, stmt: &IfStmt) -> Result<String, ()> {
+        let condition = stmt.condition.accept(self)?;
+        let then_branch = stmt.then_branch.accept(self)?;
+        match &stmt.else_branch {
+            Some(else_branch) => {
+                Ok(format!("if ({}) {} else {}", condition, then_branch, else_branch.accept(self)?))
+            },
+            None => Ok(format!("if ({}) {}", condition, then_branch)),
+        }
+    }
+
+    fn visit_print(&self, stmt: &PrintStmt) -> Result<String, ()> {
+        Ok(format!("print {};", stmt.expression.accept(self)?))
+    }
+
+    fn visit_return(&self, stmt: &ReturnStmt) -> Result<String, ()> {
+        match &stmt.value {
+            Some(value) => Ok(format!("return {};", value.accept(self)?)),
+            None => Ok("return;".to_string()),
+        }
+    }
+
+    fn visit_var(&self, stmt: &VarStmt) -> Result<String, ()> {
+        match &stmt.initializer {
+            Some(initializer) => {
+                Ok(format!("var {} = {};", stmt.name.lexeme, initializer.accept(self)?))
+            },
+            None => Ok(format!("var {};", stmt.name.lexeme)),
+        }
+    }
+
+    fn visit_while(&self, stmt: &WhileStmt) -> Result<String, ()> {
+        let condition = stmt.condition.accept(self)?;
+        Ok(format!("while ({}) {}", condition, stmt.body.accept(self)?))
+    }
+}
+
+impl ExpressionVisitor<String, ()> for PrettyPrinter<'_> {
+    fn visit_assign(&self, expr: &AssignExpr) -> Result<String, ()> {
+        let value = expr.value.accept(self)?;
+        if self.clear {
+            return Ok(format!("({} = {})", expr.name.lexeme, value));
+        }
+        Ok(format!("{} = {}", expr.name.lexeme, value))
+    }
+
     fn visit_binary(&self, expr: &BinaryExpr) -> Result<String, ()> {
         let left = expr.left.accept(self)?;
-        let operator = expr.operator.lexeme.clone();
+        let operator = &expr.operator.lexeme;
         let right = expr.right.accept(self)?;
+        let binary = match expr.operator.token_type {
+            TokenType::Comma => format!("{}{} {}", left, operator, right),
+            _ => format!("{} {} {}", left, operator, right),
+        };
         if self.clear {
-            return Ok(format!("({} {} {})", left, operator, right));
+            return Ok(format!("({})", binary));
         }
-        Ok(format!("{} {} {}", left, operator, right))
+        Ok(binary)
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> Result<String, ()> {
+        let callee = expr.callee.accept(self)?;
+        let arguments =
+            expr.arguments.iter().map(|a| a.accept(self)).collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{}({})", callee, arguments.join(", ")))
+    }
+
+    fn visit_conditional(&self, expr: &ConditionalExpr) -> Result<String, ()> {
+        let condition = expr.condition.accept(self)?;
+        let then_branch = expr.then_branch.accept(self)?;
+        let else_branch = expr.else_branch.accept(self)?;
+        if self.clear {
+            return Ok(format!("({} ? {} : {})", condition, then_branch, else_branch));
+        }
+        Ok(format!("{} ? {} : {}", condition, then_branch, else_branch))
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> Result<String, ()> {
+        Ok(format!("{}.{}", expr.object.accept(self)?, expr.name.lexeme))
     }
 
     fn visit_grouping(&self, expr: &GroupingExpr) -> Result<String, ()> {
@@ -53,9 +318,23 @@ impl ExpressionVisitor<String, ()> for PrettyPrinter {
         Ok(format!("({})", expression))
     }
 
+    /// Interpolated strings print with their original lexemes, so escapes
+    /// come out as they were written.
+    fn visit_interpolation(&self, expr: &InterpolationExpr) -> Result<String, ()> {
+        let mut result = expr.strings[0].lexeme.to_string();
+        for (expression, string) in expr.expressions.iter().zip(&expr.strings[1..]) {
+            result.push_str(&expression.accept(self)?);
+            result.push_str(string.lexeme.as_str());
+        }
+        Ok(result)
+    }
+
     fn visit_literal(&self, expr: &LiteralExpr) -> Result<String, ()> {
         let value = match expr.value {
-            LiteralValue::String(ref s) => format!("\"{}\"", s),
+            // `${` would start an interpolation when read back.
+            LiteralValue::String(s) => {
+                format!("{:?}", self.interner.resolve(s)).replace("${", "\\${")
+            },
             LiteralValue::Number(n) => format!("{}", n),
             LiteralValue::Boolean(b) => format!("{}", b),
             LiteralValue::Nil => "nil".to_string(),
@@ -63,14 +342,45 @@ impl ExpressionVisitor<String, ()> for PrettyPrinter {
         Ok(value)
     }
 
+    fn visit_logical(&self, expr: &LogicalExpr) -> Result<String, ()> {
+        let left = expr.left.accept(self)?;
+        let operator = &expr.operator.lexeme;
+        let right = expr.right.accept(self)?;
+        if self.clear {
+            return Ok(format!("({} {} {})", left, operator, right));
+        }
+        Ok(format!("{} {} {}", left, operator, right))
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> Result<String, ()> {
+        let object = expr.object.accept(self)?;
+        let value = expr.value.accept(self)?;
+        if self.clear {
+            return Ok(format!("({}.{} = {})", object, expr.name.lexeme, value));
+        }
+        Ok(format!("{}.{} = {}", object, expr.name.lexeme, value))
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> Result<String, ()> {
+        Ok(format!("super.{}", expr.method.lexeme))
+    }
+
+    fn visit_this(&self, _expr: &ThisExpr) -> Result<String, ()> {
+        Ok("this".to_string())
+    }
+
     fn visit_unary(&self, expr: &UnaryExpr) -> Result<String, ()> {
-        let operator = expr.operator.lexeme.clone();
+        let operator = &expr.operator.lexeme;
         let right = expr.right.accept(self)?;
         if self.clear {
             return Ok(format!("({}{})", operator, right));
         }
         Ok(format!("{}{}", operator, right))
     }
+
+    fn visit_variable(&self, expr: &VariableExpr) -> Result<String, ()> {
+        Ok(expr.name.lexeme.to_string())
+    }
 }
 
 #[cfg(test)]
@@ -78,7 +388,10 @@ mod tests {
     use log::LevelFilter::Trace;
 
     use super::*;
-    use crate::scanner::TokenType;
+    use crate::{
+        scanner::{Scanner, TokenType},
+        span::Spanned,
+    };
 
     fn init_logger() {
         let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
@@ -87,16 +400,61 @@ mod tests {
     #[test]
     fn test_print() {
         init_logger();
+        let mut interner = Interner::new();
         let expr = Expression::Binary(BinaryExpr {
             left: Box::new(Expression::Literal(LiteralExpr {
-                value: LiteralValue::String("Hello".to_string()),
+                value: LiteralValue::String(interner.intern("Hello")),
+                span: Span::new(0, 7),
             })),
-            operator: Token::new(TokenType::Plus, "+".to_string(), None, 0, 0),
+            operator: OwnedToken::new(TokenType::Plus, "+", None, 0, 0, Span::new(8, 9)),
             right: Box::new(Expression::Literal(LiteralExpr {
-                value: LiteralValue::String("World".to_string()),
+                value: LiteralValue::String(interner.intern("World")),
+                span: Span::new(10, 17),
             })),
         });
-        let result = expr.accept(&PrettyPrinter::default());
+        let result = expr.accept(&PrettyPrinter::new(&interner));
         assert_eq!(result.unwrap(), "\"Hello\" + \"World\"");
+        assert_eq!(expr.span(), Span::new(0, 17));
+    }
+
+    #[test]
+    fn test_spans() {
+        init_logger();
+        let source = "var sum = add(1, 2) * (x - y);\nif (sum > 3) print sum;";
+        let tokens = Scanner::new(source);
+        let statements = crate::parser::Parser::new(tokens, &mut Interner::new())
+            .parse_program()
+            .into_result()
+            .expect("Parsing failed.");
+
+        let Statement::Var(declaration) = &statements[0] else { panic!() };
+        let initializer = declaration.initializer.as_ref().unwrap();
+        assert_eq!(
+            &source[initializer.span().start..initializer.span().end],
+            "add(1, 2) * (x - y)"
+        );
+        let Statement::If(statement) = &statements[1] else { panic!() };
+        assert_eq!(
+            &source[statement.condition.span().start..statement.condition.span().end],
+            "sum > 3"
+        );
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        init_logger();
+        let source = "print 1;\n{}\nvar a = 2;\nclass C { m() { return; } }\nwhile (a) a;\n\
+                      for (var i = 0; i < 1; i = i + 1) {}\nif (a) {} else print a;";
+        let statements = crate::parser::Parser::new(Scanner::new(source), &mut Interner::new())
+            .parse_program()
+            .into_result()
+            .expect("Parsing failed.");
+        let texts: Vec<_> =
+            statements.iter().map(|s| &source[s.span().start..s.span().end]).collect();
+        assert_eq!(texts, source.lines().collect::<Vec<_>>());
+
+        let Statement::Class(class) = &statements[3] else { panic!() };
+        let Statement::Return(statement) = &class.methods[0].body[0] else { panic!() };
+        assert_eq!(&source[class.methods[0].span.start..statement.span.end], "m() { return;");
     }
 }
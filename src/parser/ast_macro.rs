@@ -1,61 +1,106 @@
+/// Generates an AST enum, one struct per variant, and a visitor trait over
+/// them.
+///
+/// Every node implements `Spanned`, covering the spans of all of its fields
+/// that have one. Each variant needs at least one field that always has a
+/// span, such as a token or an explicit `span: Span` for the parts of its
+/// syntax that have no token of their own.
+///
+/// Variants whose snake-cased name is a Rust keyword (`If`, `While`, ...)
+/// can name their constructor explicitly with `If as if_(...)`.
 #[macro_export]
 macro_rules! lox_ast {
-    ($name:ident {
+    ($name:ident: $suffix:ident {
         $(
-            $variant:ident(
-                $($field:ident: $field_type:ty),* $(,)?
+            $variant:ident $(as $constructor:ident)? (
+                $($(#[$field_meta:meta])* $field:ident: $field_type:ty),* $(,)?
             )
         ),* $(,)?
     }) => {
         paste::paste! {
-            #[derive(Debug, serde::Serialize, serde::Deserialize)]
+            #[derive(Debug)]
             pub enum $name {
                 $(
-                    $variant([<$variant Expr>])
+                    $variant([<$variant $suffix>])
                 ),*
             }
 
-            pub trait ExpressionVisitor<R, E> {
+            pub trait [<$name Visitor>]<R, E> {
                 $(
-                    fn [<visit_ $variant:snake>](&self, expr: &[<$variant Expr>]) -> Result<R, E>;
+                    fn [<visit_ $variant:snake>](&self, [<$suffix:lower>]: &[<$variant $suffix>]) -> Result<R, E>;
                 ) *
             }
 
+            impl $crate::span::Spanned for $name {
+                fn span(&self) -> $crate::span::Span {
+                    match self {
+                        $(
+                            $name::$variant([<$suffix:lower>]) => [<$suffix:lower>].span(),
+                        )*
+                    }
+                }
+            }
+
+            impl $crate::span::MaybeSpanned for $name {
+                fn maybe_span(&self) -> Option<$crate::span::Span> {
+                    Some($crate::span::Spanned::span(self))
+                }
+            }
+
             impl $name {
-                pub fn accept<R, E, T: ExpressionVisitor<R, E>>(&self, visitor: &T) -> Result<R, E> {
+                pub fn accept<R, E, T: [<$name Visitor>]<R, E>>(&self, visitor: &T) -> Result<R, E> {
                     match self {
                         $(
-                            $name::$variant(expr) => expr.accept(visitor),
+                            $name::$variant([<$suffix:lower>]) => [<$suffix:lower>].accept(visitor),
                         )*
                     }
                 }
 
                 $(
-                    pub fn [<$variant:snake>]($($field: $field_type),*) -> $name {
-                        $name::$variant([<$variant Expr>]::new($($field),*))
-                    }
+                    $crate::lox_ast!(
+                        @constructor $name, [<$variant $suffix>], [<$variant:snake>] $(as $constructor)?,
+                        ($($field: $field_type),*)
+                    );
                 ) *
             }
 
 
             $(
-                #[derive(Debug, serde::Serialize, serde::Deserialize)]
-                pub struct [<$variant Expr>] {
-                    $(pub $field: $field_type),*
+                #[derive(Debug)]
+                pub struct [<$variant $suffix>] {
+                    $($(#[$field_meta])* pub $field: $field_type),*
                 }
 
-                impl From<[<$variant Expr>]> for $name {
-                    fn from(expr: [<$variant Expr>]) -> Self {
-                        $name::$variant(expr)
+                impl From<[<$variant $suffix>]> for $name {
+                    fn from([<$suffix:lower>]: [<$variant $suffix>]) -> Self {
+                        $name::$variant([<$suffix:lower>])
                     }
                 }
 
-                impl [<$variant Expr>] {
+                impl $crate::span::Spanned for [<$variant $suffix>] {
+                    #[allow(unused_imports)]
+                    fn span(&self) -> $crate::span::Span {
+                        use $crate::span::MaybeSpanned;
+                        [$(self.$field.maybe_span()),*]
+                            .into_iter()
+                            .flatten()
+                            .reduce($crate::span::Span::merge)
+                            .expect("every node has a field with a span")
+                    }
+                }
+
+                impl $crate::span::MaybeSpanned for [<$variant $suffix>] {
+                    fn maybe_span(&self) -> Option<$crate::span::Span> {
+                        Some($crate::span::Spanned::span(self))
+                    }
+                }
+
+                impl [<$variant $suffix>] {
                     pub fn new($($field: $field_type),*) -> Self {
                         Self { $($field),* }
                     }
 
-                    pub fn accept<R, E, T: ExpressionVisitor<R, E>>(&self, visitor: &T) -> Result<R, E> {
+                    pub fn accept<R, E, T: [<$name Visitor>]<R, E>>(&self, visitor: &T) -> Result<R, E> {
                         visitor.[<visit_ $variant:snake>](&self)
                     }
                 }
@@ -63,4 +108,16 @@ macro_rules! lox_ast {
         }
 
     };
+
+    (@constructor $name:ident, $node:ident, $default:ident as $constructor:ident, ($($field:ident: $field_type:ty),*)) => {
+        pub fn $constructor($($field: $field_type),*) -> $name {
+            $node::new($($field),*).into()
+        }
+    };
+
+    (@constructor $name:ident, $node:ident, $default:ident, ($($field:ident: $field_type:ty),*)) => {
+        pub fn $default($($field: $field_type),*) -> $name {
+            $node::new($($field),*).into()
+        }
+    };
 }
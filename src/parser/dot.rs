@@ -0,0 +1,337 @@
+use std::{
+    cell::{Cell, RefCell},
+    convert::Infallible,
+};
+
+use crate::{interner::Interner, parser::ast::*, scanner::LiteralValue};
+
+/// Prints the AST as a Graphviz `digraph`, with one node per AST node and
+/// edges labelled with the role of the child, such as `left`, `right` or
+/// `operand`. Children in lists are labelled with their index, as in
+/// `arguments[0]`.
+///
+/// Visiting a node adds it to the graph and returns its id.
+pub struct DotPrinter<'a> {
+    /// Resolves string literals, which are interned by the parser.
+    interner: &'a Interner,
+    next_id: Cell<usize>,
+    lines: RefCell<Vec<String>>,
+}
+
+impl<'a> DotPrinter<'a> {
+    pub fn new(interner: &'a Interner) -> Self {
+        DotPrinter {
+            interner,
+            next_id: Cell::new(0),
+            lines: RefCell::new(vec![]),
+        }
+    }
+
+    /// The graph of a program, rooted at a node standing for the whole of it.
+    pub fn print_program(&self, statements: &[Statement]) -> String {
+        let root = self.node("program");
+        for (index, statement) in statements.iter().enumerate() {
+            let Ok(child) = statement.accept(self);
+            self.edge(root, child, &format!("statements[{}]", index));
+        }
+        self.finish()
+    }
+
+    pub fn print_expression(&self, expression: &Expression) -> String {
+        let Ok(_) = expression.accept(self);
+        self.finish()
+    }
+
+    fn finish(&self) -> String {
+        self.next_id.set(0);
+        let lines = self.lines.take();
+        let mut graph =
+            "digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n".to_string();
+        for line in lines {
+            graph.push_str(&format!("    {}\n", line));
+        }
+        graph.push('}');
+        graph
+    }
+
+    fn node(&self, label: &str) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.lines.borrow_mut().push(format!("n{} [label=\"{}\"];", id, escape(label)));
+        id
+    }
+
+    fn edge(&self, from: usize, to: usize, label: &str) {
+        self.lines.borrow_mut().push(format!(
+            "n{} -> n{} [label=\"{}\"];",
+            from,
+            to,
+            escape(label)
+        ));
+    }
+
+    fn child(&self, parent: usize, expression: &Expression, label: &str) {
+        let Ok(child) = expression.accept(self);
+        self.edge(parent, child, label);
+    }
+
+    fn statement(&self, parent: usize, statement: &Statement, label: &str) {
+        let Ok(child) = statement.accept(self);
+        self.edge(parent, child, label);
+    }
+
+    fn statements(&self, parent: usize, statements: &[Statement], field: &str) {
+        for (index, statement) in statements.iter().enumerate() {
+            self.statement(parent, statement, &format!("{}[{}]", field, index));
+        }
+    }
+
+    fn function(&self, stmt: &FunctionStmt) -> usize {
+        let params = stmt.params.iter().map(|p| p.lexeme.as_str()).collect::<Vec<_>>();
+        let id = self.node(&format!("fun {}({})", stmt.name.lexeme, params.join(", ")));
+        self.statements(id, &stmt.body, "body");
+        id
+    }
+
+    fn literal(&self, value: &LiteralValue) -> String {
+        match value {
+            LiteralValue::String(s) => format!("{:?}", self.interner.resolve(*s)),
+            LiteralValue::Number(n) => format!("{}", n),
+            LiteralValue::Boolean(b) => format!("{}", b),
+            LiteralValue::Nil => "nil".to_string(),
+        }
+    }
+}
+
+/// Escapes `label` for a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl ExpressionVisitor<usize, Infallible> for DotPrinter<'_> {
+    fn visit_assign(&self, expr: &AssignExpr) -> Result<usize, Infallible> {
+        let id = self.node(&format!("{} =", expr.name.lexeme));
+        self.child(id, &expr.value, "value");
+        Ok(id)
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> Result<usize, Infallible> {
+        let id = self.node(&expr.operator.lexeme);
+        self.child(id, &expr.left, "left");
+        self.child(id, &expr.right, "right");
+        Ok(id)
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> Result<usize, Infallible> {
+        let id = self.node("call");
+        self.child(id, &expr.callee, "callee");
+        for (index, argument) in expr.arguments.iter().enumerate() {
+            self.child(id, argument, &format!("arguments[{}]", index));
+        }
+        Ok(id)
+    }
+
+    fn visit_conditional(&self, expr: &ConditionalExpr) -> Result<usize, Infallible> {
+        let id = self.node("?:");
+        self.child(id, &expr.condition, "condition");
+        self.child(id, &expr.then_branch, "then");
+        self.child(id, &expr.else_branch, "else");
+        Ok(id)
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> Result<usize, Infallible> {
+        let id = self.node(&format!(".{}", expr.name.lexeme));
+        self.child(id, &expr.object, "object");
+        Ok(id)
+    }
+
+    fn visit_grouping(&self, expr: &GroupingExpr) -> Result<usize, Infallible> {
+        let id = self.node("group");
+        self.child(id, &expr.expression, "expression");
+        Ok(id)
+    }
+
+    /// The string parts become leaves of their own, in order with the
+    /// expressions between them.
+    fn visit_interpolation(&self, expr: &InterpolationExpr) -> Result<usize, Infallible> {
+        let id = self.node("interpolation");
+        for (index, string) in expr.strings.iter().enumerate() {
+            let text = string.literal.as_ref().map(|l| self.literal(l)).unwrap_or_default();
+            let part = self.node(&text);
+            self.edge(id, part, &format!("strings[{}]", index));
+            if let Some(expression) = expr.expressions.get(index) {
+                self.child(id, expression, &format!("expressions[{}]", index));
+            }
+        }
+        Ok(id)
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> Result<usize, Infallible> {
+        Ok(self.node(&self.literal(&expr.value)))
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> Result<usize, Infallible> {
+        let id = self.node(&expr.operator.lexeme);
+        self.child(id, &expr.left, "left");
+        self.child(id, &expr.right, "right");
+        Ok(id)
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> Result<usize, Infallible> {
+        let id = self.node(&format!(".{} =", expr.name.lexeme));
+        self.child(id, &expr.object, "object");
+        self.child(id, &expr.value, "value");
+        Ok(id)
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> Result<usize, Infallible> {
+        Ok(self.node(&format!("super.{}", expr.method.lexeme)))
+    }
+
+    fn visit_this(&self, _expr: &ThisExpr) -> Result<usize, Infallible> {
+        Ok(self.node("this"))
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> Result<usize, Infallible> {
+        let id = self.node(&expr.operator.lexeme);
+        self.child(id, &expr.right, "operand");
+        Ok(id)
+    }
+
+    fn visit_variable(&self, expr: &VariableExpr) -> Result<usize, Infallible> {
+        Ok(self.node(&expr.name.lexeme))
+    }
+}
+
+impl StatementVisitor<usize, Infallible> for DotPrinter<'_> {
+    fn visit_block(&self, stmt: &BlockStmt) -> Result<usize, Infallible> {
+        let id = self.node("block");
+        self.statements(id, &stmt.statements, "statements");
+        Ok(id)
+    }
+
+    fn visit_class(&self, stmt: &ClassStmt) -> Result<usize, Infallible> {
+        let mut label = format!("class {}", stmt.name.lexeme);
+        if let Some(superclass) = &stmt.superclass {
+            label.push_str(&format!(" < {}", superclass.name.lexeme));
+        }
+        let id = self.node(&label);
+        for (index, method) in stmt.methods.iter().enumerate() {
+            let child = self.function(method);
+            self.edge(id, child, &format!("methods[{}]", index));
+        }
+        Ok(id)
+    }
+
+    fn visit_expression(&self, stmt: &ExpressionStmt) -> Result<usize, Infallible> {
+        let id = self.node(";");
+        self.child(id, &stmt.expression, "expression");
+        Ok(id)
+    }
+
+    fn visit_function(&self, stmt: &FunctionStmt) -> Result<usize, Infallible> {
+        Ok(self.function(stmt))
+    }
+
+    fn visit_if(&self, stmt: &IfStmt) -> Result<usize, Infallible> {
+        let id = self.node("if");
+        self.child(id, &stmt.condition, "condition");
+        self.statement(id, &stmt.then_branch, "then");
+        if let Some(else_branch) = &stmt.else_branch {
+            self.statement(id, else_branch, "else");
+        }
+        Ok(id)
+    }
+
+    fn visit_print(&self, stmt: &PrintStmt) -> Result<usize, Infallible> {
+        let id = self.node("print");
+        self.child(id, &stmt.expression, "expression");
+        Ok(id)
+    }
+
+    fn visit_return(&self, stmt: &ReturnStmt) -> Result<usize, Infallible> {
+        let id = self.node("return");
+        if let Some(value) = &stmt.value {
+            self.child(id, value, "value");
+        }
+        Ok(id)
+    }
+
+    fn visit_var(&self, stmt: &VarStmt) -> Result<usize, Infallible> {
+        let id = self.node(&format!("var {}", stmt.name.lexeme));
+        if let Some(initializer) = &stmt.initializer {
+            self.child(id, initializer, "initializer");
+        }
+        Ok(id)
+    }
+
+    fn visit_while(&self, stmt: &WhileStmt) -> Result<usize, Infallible> {
+        let id = self.node("while");
+        self.child(id, &stmt.condition, "condition");
+        self.statement(id, &stmt.body, "body");
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter::Trace;
+
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
+    }
+
+    #[test]
+    fn test_expression() {
+        init_logger();
+        let mut interner = Interner::new();
+        let expression =
+            Parser::new(Scanner::new("-a + \"b\\\"\" * 2"), &mut interner).parse().unwrap();
+        let printer = DotPrinter::new(&interner);
+        let expected = r#"digraph ast {
+    node [shape=box, fontname="monospace"];
+    n0 [label="+"];
+    n1 [label="-"];
+    n2 [label="a"];
+    n1 -> n2 [label="operand"];
+    n0 -> n1 [label="left"];
+    n3 [label="*"];
+    n4 [label="\"b\\\"\""];
+    n3 -> n4 [label="left"];
+    n5 [label="2"];
+    n3 -> n5 [label="right"];
+    n0 -> n3 [label="right"];
+}"#;
+        assert_eq!(printer.print_expression(&expression), expected);
+        // The printer starts over for every graph.
+        assert_eq!(printer.print_expression(&expression), expected);
+    }
+
+    #[test]
+    fn test_program() {
+        init_logger();
+        let source = "fun f(x) { if (x) return x; } print f(1);";
+        let mut interner = Interner::new();
+        let statements =
+            Parser::new(Scanner::new(source), &mut interner).parse_program().into_result().unwrap();
+        let graph = DotPrinter::new(&interner).print_program(&statements);
+        for line in [
+            "n0 [label=\"program\"];",
+            "n1 [label=\"fun f(x)\"];",
+            "n2 [label=\"if\"];",
+            "n2 -> n3 [label=\"condition\"];",
+            "n4 [label=\"return\"];",
+            "n2 -> n4 [label=\"then\"];",
+            "n1 -> n2 [label=\"body[0]\"];",
+            "n0 -> n1 [label=\"statements[0]\"];",
+            "n7 -> n8 [label=\"callee\"];",
+            "n7 -> n9 [label=\"arguments[0]\"];",
+            "n0 -> n6 [label=\"statements[1]\"];",
+        ] {
+            assert!(graph.contains(line), "{} not in\n{}", line, graph);
+        }
+    }
+}
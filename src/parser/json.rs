@@ -0,0 +1,819 @@
+//! A stable JSON form of the AST for tools outside the interpreter.
+//!
+//! The format does not follow the Rust types. It is versioned and only
+//! changes along with [`VERSION`]:
+//!
+//! ```text
+//! document  → { "version": 1, "statements": [ statement* ] }
+//! node      → { "kind": KIND, "span": span, ...fields }
+//! span      → { "start": OFFSET, "end": OFFSET }
+//! token     → { "text": LEXEME, "span": span, "line": LINE }
+//! ```
+//!
+//! Every statement and expression is a node whose `kind` is its variant name
+//! (`"Binary"`, `"While"`, ...) and whose other fields are named as in
+//! [`ast`](super::ast). Names, operators and keywords are tokens. Literal
+//! values are plain JSON values, with `null` for `nil`, and the parts of an
+//! interpolated string are tokens with the text they stand for in `value`.
+//!
+//! Spans are byte offsets into the source. Those of statements, tokens,
+//! literals and groupings are read back; the rest are computed from them, as
+//! for parsed nodes. Tokens also carry the line they are on, counting from 1,
+//! which runtime errors report.
+
+use std::{cell::RefCell, convert::Infallible, fmt::Display, rc::Rc};
+
+use serde_json::{Map, Number, Value, json};
+
+use crate::{
+    interner::{Interner, Symbol},
+    parser::{
+        ast::*,
+        operators::{Fixity, Operation, lookup},
+    },
+    scanner::{LiteralValue, OwnedToken, Scanner, TokenType},
+    span::{Span, Spanned},
+};
+
+/// The version of the format written by [`to_json`] and read by
+/// [`from_json`].
+pub const VERSION: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    /// The text is not JSON at all.
+    Syntax(String),
+    UnsupportedVersion(Value),
+    /// The value at `path`, such as `statements[0].expression.left`, does
+    /// not fit the format.
+    Invalid {
+        path: String,
+        message: String,
+    },
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax(message) => write!(f, "Invalid JSON: {}", message),
+            JsonError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported AST format version {}, expected {}.", version, VERSION)
+            },
+            JsonError::Invalid {
+                path,
+                message,
+            } => write!(f, "Invalid AST at {}: {}.", path, message),
+        }
+    }
+}
+
+/// Writes a program parsed with `interner`, which holds its string values.
+pub fn to_json(statements: &[Statement], interner: &Interner) -> Value {
+    let writer = JsonWriter {
+        interner,
+    };
+    let statements: Vec<_> = statements.iter().map(|s| writer.statement(s)).collect();
+    json!({ "version": VERSION, "statements": statements })
+}
+
+pub fn expression_to_json(expression: &Expression, interner: &Interner) -> Value {
+    JsonWriter {
+        interner,
+    }
+    .expression(expression)
+}
+
+/// Reads a document written by [`to_json`], or by any tool following the
+/// format. Names and string values are interned with `interner`.
+pub fn from_json(document: &Value, interner: &mut Interner) -> Result<Vec<Statement>, JsonError> {
+    let interner = RefCell::new(interner);
+    let document = Reader::root(document, &interner);
+    let version = document.field("version")?;
+    if version.value.as_u64() != Some(VERSION) {
+        return Err(JsonError::UnsupportedVersion(version.value.clone()));
+    }
+    document.field("statements")?.items()?.iter().map(statement).collect()
+}
+
+/// Like [`from_json`], for a document that is still text.
+pub fn load(text: &str, interner: &mut Interner) -> Result<Vec<Statement>, JsonError> {
+    let document: Value =
+        serde_json::from_str(text).map_err(|e| JsonError::Syntax(e.to_string()))?;
+    from_json(&document, interner)
+}
+
+/// Reads a single expression node, without the document around it.
+pub fn expression_from_json(
+    node: &Value,
+    interner: &mut Interner,
+) -> Result<Expression, JsonError> {
+    expression(&Reader::root(node, &RefCell::new(interner)))
+}
+
+struct JsonWriter<'a> {
+    interner: &'a Interner,
+}
+
+impl JsonWriter<'_> {
+    fn expression(&self, expression: &Expression) -> Value {
+        let Ok(value) = expression.accept(self);
+        value
+    }
+
+    fn statement(&self, statement: &Statement) -> Value {
+        let Ok(value) = statement.accept(self);
+        value
+    }
+
+    fn statements(&self, statements: &[Statement]) -> Value {
+        statements.iter().map(|s| self.statement(s)).collect()
+    }
+
+    fn function(&self, stmt: &FunctionStmt) -> Value {
+        node("Function", stmt.span(), [
+            ("name", token(&stmt.name)),
+            ("params", stmt.params.iter().map(token).collect()),
+            ("body", self.statements(&stmt.body)),
+        ])
+    }
+
+    fn literal(&self, value: &LiteralValue) -> Value {
+        match value {
+            LiteralValue::String(s) => self.interner.resolve(*s).into(),
+            LiteralValue::Number(n) => (*n).into(),
+            LiteralValue::Boolean(b) => (*b).into(),
+            LiteralValue::Nil => Value::Null,
+        }
+    }
+}
+
+fn node<const N: usize>(kind: &str, span: Span, fields: [(&str, Value); N]) -> Value {
+    let mut object = Map::new();
+    object.insert("kind".to_string(), kind.into());
+    object.insert("span".to_string(), span_json(span));
+    for (name, value) in fields {
+        object.insert(name.to_string(), value);
+    }
+    Value::Object(object)
+}
+
+fn span_json(span: Span) -> Value {
+    json!({ "start": span.start, "end": span.end })
+}
+
+fn token(token: &OwnedToken) -> Value {
+    json!({ "text": token.lexeme, "span": span_json(token.span), "line": token.line })
+}
+
+impl ExpressionVisitor<Value, Infallible> for JsonWriter<'_> {
+    fn visit_assign(&self, expr: &AssignExpr) -> Result<Value, Infallible> {
+        Ok(node("Assign", expr.span(), [
+            ("name", token(&expr.name)),
+            ("value", self.expression(&expr.value)),
+        ]))
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> Result<Value, Infallible> {
+        Ok(node("Binary", expr.span(), [
+            ("left", self.expression(&expr.left)),
+            ("operator", token(&expr.operator)),
+            ("right", self.expression(&expr.right)),
+        ]))
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> Result<Value, Infallible> {
+        Ok(node("Call", expr.span(), [
+            ("callee", self.expression(&expr.callee)),
+            ("paren", token(&expr.paren)),
+            ("arguments", expr.arguments.iter().map(|a| self.expression(a)).collect()),
+        ]))
+    }
+
+    fn visit_conditional(&self, expr: &ConditionalExpr) -> Result<Value, Infallible> {
+        Ok(node("Conditional", expr.span(), [
+            ("condition", self.expression(&expr.condition)),
+            ("then_branch", self.expression(&expr.then_branch)),
+            ("else_branch", self.expression(&expr.else_branch)),
+        ]))
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> Result<Value, Infallible> {
+        Ok(node("Get", expr.span(), [
+            ("object", self.expression(&expr.object)),
+            ("name", token(&expr.name)),
+        ]))
+    }
+
+    fn visit_grouping(&self, expr: &GroupingExpr) -> Result<Value, Infallible> {
+        Ok(node("Grouping", expr.span, [("expression", self.expression(&expr.expression))]))
+    }
+
+    fn visit_interpolation(&self, expr: &InterpolationExpr) -> Result<Value, Infallible> {
+        let strings = expr
+            .strings
+            .iter()
+            .map(|string| {
+                let mut part = token(string);
+                part["value"] =
+                    string.literal.as_ref().map(|l| self.literal(l)).unwrap_or(Value::Null);
+                part
+            })
+            .collect();
+        Ok(node("Interpolation", expr.span(), [
+            ("strings", strings),
+            ("expressions", expr.expressions.iter().map(|e| self.expression(e)).collect()),
+        ]))
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> Result<Value, Infallible> {
+        Ok(node("Literal", expr.span, [("value", self.literal(&expr.value))]))
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> Result<Value, Infallible> {
+        Ok(node("Logical", expr.span(), [
+            ("left", self.expression(&expr.left)),
+            ("operator", token(&expr.operator)),
+            ("right", self.expression(&expr.right)),
+        ]))
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> Result<Value, Infallible> {
+        Ok(node("Set", expr.span(), [
+            ("object", self.expression(&expr.object)),
+            ("name", token(&expr.name)),
+            ("value", self.expression(&expr.value)),
+        ]))
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> Result<Value, Infallible> {
+        Ok(node("Super", expr.span(), [
+            ("keyword", token(&expr.keyword)),
+            ("method", token(&expr.method)),
+        ]))
+    }
+
+    fn visit_this(&self, expr: &ThisExpr) -> Result<Value, Infallible> {
+        Ok(node("This", expr.span(), [("keyword", token(&expr.keyword))]))
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> Result<Value, Infallible> {
+        Ok(node("Unary", expr.span(), [
+            ("operator", token(&expr.operator)),
+            ("right", self.expression(&expr.right)),
+        ]))
+    }
+
+    fn visit_variable(&self, expr: &VariableExpr) -> Result<Value, Infallible> {
+        Ok(node("Variable", expr.span(), [("name", token(&expr.name))]))
+    }
+}
+
+impl StatementVisitor<Value, Infallible> for JsonWriter<'_> {
+    fn visit_block(&self, stmt: &BlockStmt) -> Result<Value, Infallible> {
+        Ok(node("Block", stmt.span(), [("statements", self.statements(&stmt.statements))]))
+    }
+
+    fn visit_class(&self, stmt: &ClassStmt) -> Result<Value, Infallible> {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => superclass.accept(self)?,
+            None => Value::Null,
+        };
+        Ok(node("Class", stmt.span(), [
+            ("name", token(&stmt.name)),
+            ("superclass", superclass),
+            ("methods", stmt.methods.iter().map(|m| self.function(m)).collect()),
+        ]))
+    }
+
+    fn visit_expression(&self, stmt: &ExpressionStmt) -> Result<Value, Infallible> {
+        Ok(node("Expression", stmt.span(), [("expression", self.expression(&stmt.expression))]))
+    }
+
+    fn visit_function(&self, stmt: &FunctionStmt) -> Result<Value, Infallible> {
+        Ok(self.function(stmt))
+    }
+
+    fn visit_if(&self, stmt: &IfStmt) -> Result<Value, Infallible> {
+        let else_branch = stmt.else_branch.as_ref().map(|s| self.statement(s));
+        Ok(node("If", stmt.span(), [
+            ("condition", self.expression(&stmt.condition)),
+            ("then_branch", self.statement(&stmt.then_branch)),
+            ("else_branch", else_branch.unwrap_or(Value::Null)),
+        ]))
+    }
+
+    fn visit_print(&self, stmt: &PrintStmt) -> Result<Value, Infallible> {
+        Ok(node("Print", stmt.span(), [("expression", self.expression(&stmt.expression))]))
+    }
+
+    fn visit_return(&self, stmt: &ReturnStmt) -> Result<Value, Infallible> {
+        let value = stmt.value.as_ref().map(|e| self.expression(e));
+        Ok(node("Return", stmt.span(), [
+            ("keyword", token(&stmt.keyword)),
+            ("value", value.unwrap_or(Value::Null)),
+        ]))
+    }
+
+    fn visit_var(&self, stmt: &VarStmt) -> Result<Value, Infallible> {
+        let initializer = stmt.initializer.as_ref().map(|e| self.expression(e));
+        Ok(node("Var", stmt.span(), [
+            ("name", token(&stmt.name)),
+            ("initializer", initializer.unwrap_or(Value::Null)),
+        ]))
+    }
+
+    fn visit_while(&self, stmt: &WhileStmt) -> Result<Value, Infallible> {
+        Ok(node("While", stmt.span(), [
+            ("condition", self.expression(&stmt.condition)),
+            ("body", self.statement(&stmt.body)),
+        ]))
+    }
+}
+
+/// A value in the document being loaded, along with where it is, for errors.
+struct Reader<'a> {
+    value: &'a Value,
+    path: String,
+    interner: &'a RefCell<&'a mut Interner>,
+}
+
+impl<'a> Reader<'a> {
+    fn root(value: &'a Value, interner: &'a RefCell<&'a mut Interner>) -> Self {
+        Reader {
+            value,
+            path: "$".to_string(),
+            interner,
+        }
+    }
+
+    fn intern(&self, text: &str) -> Symbol {
+        self.interner.borrow_mut().intern(text)
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> JsonError {
+        JsonError::Invalid {
+            path: self.path.clone(),
+            message: message.into(),
+        }
+    }
+
+    fn field(&self, name: &str) -> Result<Reader<'a>, JsonError> {
+        self.optional(name)?.ok_or_else(|| self.invalid(format!("missing field '{}'", name)))
+    }
+
+    /// A field that may be missing or `null`.
+    fn optional(&self, name: &str) -> Result<Option<Reader<'a>>, JsonError> {
+        let object = self.value.as_object().ok_or_else(|| self.invalid("expected an object"))?;
+        Ok(object.get(name).filter(|value| !value.is_null()).map(|value| Reader {
+            value,
+            path: format!("{}.{}", self.path, name),
+            interner: self.interner,
+        }))
+    }
+
+    fn items(&self) -> Result<Vec<Reader<'a>>, JsonError> {
+        let array = self.value.as_array().ok_or_else(|| self.invalid("expected an array"))?;
+        Ok(array
+            .iter()
+            .enumerate()
+            .map(|(index, value)| Reader {
+                value,
+                path: format!("{}[{}]", self.path, index),
+                interner: self.interner,
+            })
+            .collect())
+    }
+
+    fn str(&self) -> Result<&'a str, JsonError> {
+        self.value.as_str().ok_or_else(|| self.invalid("expected a string"))
+    }
+
+    /// A non-negative integer, which `description` says the meaning of for
+    /// errors.
+    fn number(&self, description: &str) -> Result<usize, JsonError> {
+        self.value
+            .as_u64()
+            .and_then(|number| usize::try_from(number).ok())
+            .ok_or_else(|| self.invalid(format!("expected {}", description)))
+    }
+
+    fn kind(&self) -> Result<&'a str, JsonError> {
+        self.field("kind")?.str()
+    }
+
+    fn span(&self) -> Result<Span, JsonError> {
+        let span = self.field("span")?;
+        let start = span.field("start")?.number("a byte offset")?;
+        let end = span.field("end")?.number("a byte offset")?;
+        if end < start {
+            return Err(span.invalid("span ends before it starts"));
+        }
+        Ok(Span::new(start, end))
+    }
+
+    fn boxed(&self, name: &str) -> Result<Box<Expression>, JsonError> {
+        Ok(Box::new(expression(&self.field(name)?)?))
+    }
+
+    fn expressions(&self, name: &str) -> Result<Vec<Expression>, JsonError> {
+        self.field(name)?.items()?.iter().map(expression).collect()
+    }
+
+    fn statements(&self, name: &str) -> Result<Vec<Statement>, JsonError> {
+        self.field(name)?.items()?.iter().map(statement).collect()
+    }
+
+    fn token(
+        &self,
+        name: &str,
+        description: &str,
+        expected: impl Fn(&TokenType) -> bool,
+    ) -> Result<OwnedToken, JsonError> {
+        self.field(name)?.token_value(description, expected)
+    }
+
+    /// Reads a token whose text scans as a single token that `expected`
+    /// accepts. `description` says what that is for errors.
+    fn token_value(
+        &self,
+        description: &str,
+        expected: impl Fn(&TokenType) -> bool,
+    ) -> Result<OwnedToken, JsonError> {
+        let text = self.field("text")?.str()?;
+        let mut scanner = Scanner::new(text);
+        let scanned = match (scanner.next(), scanner.next()) {
+            (Some(Ok(token)), Some(Ok(end)))
+                if end.token_type == TokenType::Eof
+                    && token.span == Span::new(0, text.len())
+                    && expected(&token.token_type) =>
+            {
+                token
+            },
+            _ => return Err(self.invalid(format!("expected {}, found '{}'", description, text))),
+        };
+        let mut token = scanned.to_owned_token(&mut self.interner.borrow_mut());
+        token.line = self.field("line")?.number("a line number")?;
+        token.character = 0;
+        token.span = self.span()?;
+        Ok(token)
+    }
+
+    fn identifier(&self, name: &str) -> Result<OwnedToken, JsonError> {
+        self.token(name, "an identifier", |t| *t == TokenType::Identifier)
+    }
+
+    fn keyword(&self, name: &str, keyword: TokenType) -> Result<OwnedToken, JsonError> {
+        let description = format!("'{}'", keyword);
+        self.token(name, &description, |t| *t == keyword)
+    }
+}
+
+/// The value of `number` as a Lox number, unless it would change on the way,
+/// as integers beyond 2^53 do.
+fn float(number: &Number) -> Option<f64> {
+    let float = number.as_f64()?;
+    let exact = match (number.as_u64(), number.as_i64()) {
+        (Some(n), _) => float as u128 == u128::from(n),
+        (_, Some(n)) => float as i128 == i128::from(n),
+        _ => true,
+    };
+    exact.then_some(float)
+}
+
+fn operation(token_type: &TokenType) -> Option<Operation> {
+    lookup(token_type, Fixity::Infix).map(|rule| rule.operation)
+}
+
+fn expression(node: &Reader<'_>) -> Result<Expression, JsonError> {
+    let expression = match node.kind()? {
+        "Assign" => {
+            Expression::assign(NodeId::fresh(), node.identifier("name")?, node.boxed("value")?)
+        },
+        "Binary" => Expression::binary(
+            node.boxed("left")?,
+            node.token("operator", "a binary operator", |t| {
+                operation(t) == Some(Operation::Binary)
+            })?,
+            node.boxed("right")?,
+        ),
+        "Call" => Expression::call(
+            node.boxed("callee")?,
+            node.keyword("paren", TokenType::RightParen)?,
+            node.expressions("arguments")?,
+        ),
+        "Conditional" => Expression::conditional(
+            node.boxed("condition")?,
+            node.boxed("then_branch")?,
+            node.boxed("else_branch")?,
+        ),
+        "Get" => Expression::get(node.boxed("object")?, node.identifier("name")?),
+        "Grouping" => Expression::grouping(node.boxed("expression")?, node.span()?),
+        "Interpolation" => interpolation(node)?,
+        "Literal" => {
+            let value = node.optional("value")?;
+            let value = match value.map(|value| value.value) {
+                None => LiteralValue::Nil,
+                Some(Value::String(s)) => LiteralValue::String(node.intern(s)),
+                Some(Value::Number(n)) => LiteralValue::Number(float(n).ok_or_else(|| {
+                    node.invalid(format!("expected a number that fits in a double, found {}", n))
+                })?),
+                Some(Value::Bool(b)) => LiteralValue::Boolean(*b),
+                Some(_) => {
+                    return Err(node.invalid("expected a string, number, boolean or null value"));
+                },
+            };
+            Expression::literal(value, node.span()?)
+        },
+        "Logical" => Expression::logical(
+            node.boxed("left")?,
+            node.token("operator", "'and' or 'or'", |t| operation(t) == Some(Operation::Logical))?,
+            node.boxed("right")?,
+        ),
+        "Set" => {
+            Expression::set(node.boxed("object")?, node.identifier("name")?, node.boxed("value")?)
+        },
+        "Super" => Expression::super_(
+            NodeId::fresh(),
+            node.keyword("keyword", TokenType::Super)?,
+            node.identifier("method")?,
+        ),
+        "This" => Expression::this(NodeId::fresh(), node.keyword("keyword", TokenType::This)?),
+        "Unary" => Expression::unary(
+            node.token("operator", "a unary operator", |t| lookup(t, Fixity::Prefix).is_some())?,
+            node.boxed("right")?,
+        ),
+        "Variable" => Expression::variable(NodeId::fresh(), node.identifier("name")?),
+        kind => return Err(node.invalid(format!("unknown expression kind '{}'", kind))),
+    };
+    Ok(expression)
+}
+
+/// The string parts of an interpolation are not scanned again, since they
+/// are not tokens on their own. Their types follow from where they are.
+fn interpolation(node: &Reader<'_>) -> Result<Expression, JsonError> {
+    let parts = node.field("strings")?.items()?;
+    let expressions = node.expressions("expressions")?;
+    if parts.len() != expressions.len() + 1 {
+        return Err(node.invalid("expected one more string than there are expressions"));
+    }
+    let strings = parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            let token_type = match index {
+                0 => TokenType::StringStart,
+                i if i == expressions.len() => TokenType::StringEnd,
+                _ => TokenType::StringMiddle,
+            };
+            Ok(OwnedToken::new(
+                token_type,
+                part.field("text")?.str()?,
+                Some(LiteralValue::String(part.intern(part.field("value")?.str()?))),
+                part.field("line")?.number("a line number")?,
+                0,
+                part.span()?,
+            ))
+        })
+        .collect::<Result<_, JsonError>>()?;
+    Ok(Expression::interpolation(strings, expressions))
+}
+
+fn statement(node: &Reader<'_>) -> Result<Statement, JsonError> {
+    let span = node.span()?;
+    let statement = match node.kind()? {
+        "Block" => Statement::block(node.statements("statements")?, span),
+        "Class" => {
+            let superclass = match node.optional("superclass")? {
+                Some(superclass) => match expression(&superclass)? {
+                    Expression::Variable(variable) => Some(variable),
+                    _ => return Err(superclass.invalid("expected a 'Variable' node")),
+                },
+                None => None,
+            };
+            let methods = node
+                .field("methods")?
+                .items()?
+                .iter()
+                .map(|method| match statement(method)? {
+                    Statement::Function(function) => Ok(function),
+                    _ => Err(method.invalid("expected a 'Function' node")),
+                })
+                .collect::<Result<_, _>>()?;
+            Statement::class(node.identifier("name")?, superclass, methods, span)
+        },
+        "Expression" => Statement::expression(expression(&node.field("expression")?)?, span),
+        "Function" => {
+            let params = node
+                .field("params")?
+                .items()?
+                .iter()
+                .map(|param| param.token_value("an identifier", |t| *t == TokenType::Identifier))
+                .collect::<Result<_, _>>()?;
+            let body = Rc::new(node.statements("body")?);
+            Statement::function(node.identifier("name")?, params, body, span)
+        },
+        "If" => Statement::if_(
+            expression(&node.field("condition")?)?,
+            Box::new(statement(&node.field("then_branch")?)?),
+            node.optional("else_branch")?.map(|s| statement(&s).map(Box::new)).transpose()?,
+            span,
+        ),
+        "Print" => Statement::print(expression(&node.field("expression")?)?, span),
+        "Return" => Statement::return_(
+            node.keyword("keyword", TokenType::Return)?,
+            node.optional("value")?.map(|e| expression(&e)).transpose()?,
+            span,
+        ),
+        "Var" => Statement::var(
+            node.identifier("name")?,
+            node.optional("initializer")?.map(|e| expression(&e)).transpose()?,
+            span,
+        ),
+        "While" => Statement::while_(
+            expression(&node.field("condition")?)?,
+            Box::new(statement(&node.field("body")?)?),
+            span,
+        ),
+        kind => return Err(node.invalid(format!("unknown statement kind '{}'", kind))),
+    };
+    Ok(statement)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter::Trace;
+
+    use super::*;
+    use crate::{interpreter::Interpreter, parser::Parser, resolver::Resolver};
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
+    }
+
+    fn parse(source: &str, interner: &mut Interner) -> Vec<Statement> {
+        let mut parser = Parser::new(Scanner::new(source), interner);
+        parser.parse_program().into_result().expect("Parsing failed.")
+    }
+
+    /// The document for `source`, parsed with an interner of its own.
+    fn document_of(source: &str) -> Value {
+        let mut interner = Interner::new();
+        to_json(&parse(source, &mut interner), &interner)
+    }
+
+    const PROGRAM: &str = "
+        class Animal { init(name) { this.name = name; } speak() { return \"...\"; } }
+        class Dog < Animal {
+            speak() { return super.speak() + \"${this.name} says ${\"woof\"}\"; }
+        }
+        fun count(n) {
+            var i = 0;
+            while (i < n) { i = i + 1; }
+            if (!(i == n) or false) return; else print i > 2 ? -i : nil, i;
+        }
+        var dog = Dog(\"Rex\");
+        print dog.speak();
+        count(3);
+    ";
+
+    #[test]
+    fn test_round_trip() {
+        init_logger();
+        let mut interner = Interner::new();
+        let statements = parse(PROGRAM, &mut interner);
+        let document = to_json(&statements, &interner);
+        // Loading needs no part of the interner the program was parsed with.
+        let mut loaded_interner = Interner::new();
+        let loaded = from_json(&document, &mut loaded_interner).expect("Loading failed.");
+
+        assert_eq!(to_json(&loaded, &loaded_interner), document);
+        assert_eq!(
+            PrettyPrinter::clear(&loaded_interner).print_program(&loaded),
+            PrettyPrinter::clear(&interner).print_program(&statements)
+        );
+        let spans =
+            |statements: &[Statement]| statements.iter().map(Spanned::span).collect::<Vec<_>>();
+        assert_eq!(spans(&loaded), spans(&statements));
+
+        let text = serde_json::to_string(&document).unwrap();
+        let loaded = load(&text, &mut loaded_interner).unwrap();
+        assert_eq!(to_json(&loaded, &loaded_interner), document);
+    }
+
+    #[test]
+    fn test_loaded_program_runs() {
+        init_logger();
+        let output = crate::interpreter::CapturedOutput::default();
+        let interpreter = Interpreter::with_output(output.clone());
+        let statements =
+            load(&document_of(PROGRAM).to_string(), &mut interpreter.interner()).unwrap();
+        interpreter.resolve(Resolver::new().resolve(&statements).unwrap());
+        interpreter.interpret(&statements).unwrap();
+        assert_eq!(output.take(), "...Rex says woof\n3\n");
+    }
+
+    #[test]
+    fn test_expression_format() {
+        init_logger();
+        let mut interner = Interner::new();
+        let expression = Parser::new(Scanner::new("-a + 2"), &mut interner).parse().unwrap();
+        let node = expression_to_json(&expression, &interner);
+        assert_eq!(
+            node,
+            json!({
+                "kind": "Binary",
+                "span": { "start": 0, "end": 6 },
+                "left": {
+                    "kind": "Unary",
+                    "span": { "start": 0, "end": 2 },
+                    "operator": { "text": "-", "span": { "start": 0, "end": 1 }, "line": 1 },
+                    "right": {
+                        "kind": "Variable",
+                        "span": { "start": 1, "end": 2 },
+                        "name": { "text": "a", "span": { "start": 1, "end": 2 }, "line": 1 },
+                    },
+                },
+                "operator": { "text": "+", "span": { "start": 3, "end": 4 }, "line": 1 },
+                "right": { "kind": "Literal", "span": { "start": 5, "end": 6 }, "value": 2.0 },
+            })
+        );
+        let loaded = expression_from_json(&node, &mut interner).unwrap();
+        assert_eq!(expression_to_json(&loaded, &interner), node);
+        let Expression::Binary(binary) = loaded else { panic!("expected a binary expression") };
+        assert_eq!(binary.operator.token_type, TokenType::Plus);
+    }
+
+    #[test]
+    fn test_generated_ast() {
+        init_logger();
+        // Written by hand, as another tool would: only spans that are read
+        // back are given, and nil literals leave out their value.
+        let document = json!({
+            "version": 1,
+            "statements": [{
+                "kind": "Print",
+                "span": { "start": 0, "end": 0 },
+                "expression": {
+                    "kind": "Logical",
+                    "left": { "kind": "Literal", "span": { "start": 0, "end": 0 } },
+                    "operator": { "text": "or", "span": { "start": 0, "end": 0 }, "line": 1 },
+                    "right": { "kind": "Literal", "value": "fallback", "span": { "start": 0, "end": 0 } },
+                },
+            }],
+        });
+        let mut interner = Interner::new();
+        let statements = from_json(&document, &mut interner).unwrap();
+        assert_eq!(
+            PrettyPrinter::new(&interner).print_program(&statements),
+            "print nil or \"fallback\";"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        init_logger();
+        let error =
+            |document: Value| from_json(&document, &mut Interner::new()).unwrap_err().to_string();
+        assert_eq!(
+            error(json!({ "version": 2, "statements": [] })),
+            "Unsupported AST format version 2, expected 1."
+        );
+        assert_eq!(
+            error(json!({ "statements": [] })),
+            "Invalid AST at $: missing field 'version'."
+        );
+
+        let mut document = document_of("print 1 + 2;");
+        document["statements"][0]["expression"]["operator"]["text"] = "and".into();
+        assert_eq!(
+            error(document.clone()),
+            "Invalid AST at $.statements[0].expression.operator: expected a binary operator, found 'and'."
+        );
+        document["statements"][0]["expression"]["kind"] = "Ternary".into();
+        assert_eq!(
+            error(document.clone()),
+            "Invalid AST at $.statements[0].expression: unknown expression kind 'Ternary'."
+        );
+
+        let mut document = document_of("var x = 1;");
+        document["statements"][0]["name"]["text"] = "x y".into();
+        assert_eq!(
+            error(document),
+            "Invalid AST at $.statements[0].name: expected an identifier, found 'x y'."
+        );
+
+        let mut document = document_of("print 1;");
+        document["statements"][0]["expression"]["value"] = u64::MAX.into();
+        assert_eq!(
+            error(document.clone()),
+            "Invalid AST at $.statements[0].expression: expected a number that fits in a double, \
+             found 18446744073709551615."
+        );
+        document["statements"][0]["expression"]["value"] = (1_u64 << 60).into();
+        assert!(from_json(&document, &mut Interner::new()).is_ok());
+
+        assert!(
+            load("{", &mut Interner::new()).unwrap_err().to_string().starts_with("Invalid JSON: ")
+        );
+    }
+}
@@ -1,120 +1,510 @@
-use std::fmt::Display;
+use std::{collections::VecDeque, fmt::Display, rc::Rc};
+
+use log::debug;
 
 use crate::{
-    parser::ast::Expression,
-    scanner::{LiteralValue, Token, TokenType},
+    diagnostic::Diagnostic,
+    interner::Interner,
+    parser::{
+        ast::{Expression, FunctionStmt, NodeId, Statement, VariableExpr},
+        operators::{Fixity, Operation, Precedence, Rule, lookup},
+    },
+    scanner::{LiteralValue, OwnedToken, ScannerError, Token, TokenType},
+    span::{Span, Spanned},
 };
 
 pub mod ast;
 mod ast_macro;
+pub mod dot;
+pub mod json;
+mod operators;
+pub mod sexpr;
+
+/// Calls and function declarations accept at most this many arguments.
+const MAX_ARGUMENTS: usize = 255;
+
+/// Anything the parser can pull tokens from, usually a [`Scanner`].
+///
+/// [`Scanner`]: crate::scanner::Scanner
+pub trait TokenStream<'src>: Iterator<Item = Result<Token<'src>, ScannerError>> {}
+
+impl<'src, I: Iterator<Item = Result<Token<'src>, ScannerError>>> TokenStream<'src> for I {}
+
+/// Pulls tokens from the stream only as far as it needs to look ahead, so
+/// the whole token list is never held in memory.
+pub struct Parser<'src, 'i, I> {
+    tokens: I,
+    /// Interns names and string values for the AST.
+    interner: &'i mut Interner,
+    /// Tokens pulled from `tokens` but not consumed yet. Never empty once
+    /// the parser is constructed; the end of the stream reads as `Eof`.
+    lookahead: VecDeque<Token<'src>>,
+    previous: Option<Token<'src>>,
+    /// Errors that did not stop parsing, in the order they were found.
+    errors: Vec<ParserError>,
+}
+
+/// Every statement that parsed, along with every syntax error found on the
+/// way. A declaration that fails to parse is left out and parsing picks up
+/// again at the next statement, so a block or function body around it keeps
+/// its other statements. Errors that do not stop parsing, such as too many
+/// arguments, leave their statement in.
+#[derive(Debug)]
+pub struct ParsedProgram {
+    pub statements: Vec<Statement>,
+    pub errors: Vec<ParserError>,
+}
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+impl ParsedProgram {
+    pub fn into_result(self) -> Result<Vec<Statement>, Vec<ParserError>> {
+        if self.errors.is_empty() { Ok(self.statements) } else { Err(self.errors) }
+    }
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser {
+impl<'src, 'i, I: TokenStream<'src>> Parser<'src, 'i, I> {
+    pub fn new(tokens: I, interner: &'i mut Interner) -> Self {
+        let mut parser = Parser {
             tokens,
-            current: 0,
-        }
+            interner,
+            lookahead: VecDeque::new(),
+            previous: None,
+            errors: vec![],
+        };
+        parser.fill(1);
+        parser
     }
 
     pub fn parse(&mut self) -> Result<Expression, ParserError> {
-        self.parse_expression()
+        let expression = self.parse_expression()?;
+        match self.errors.drain(..).next() {
+            Some(error) => Err(error),
+            None => Ok(expression),
+        }
     }
 
-    fn parse_expression(&mut self) -> Result<Expression, ParserError> {
-        self.parse_equality()
+    /// program → declaration* EOF ;
+    pub fn parse_program(&mut self) -> ParsedProgram {
+        let mut statements = vec![];
+        while !self.is_at_end() {
+            statements.extend(self.parse_declaration());
+        }
+        ParsedProgram {
+            statements,
+            errors: std::mem::take(&mut self.errors),
+        }
     }
 
-    /// equality → comparison ( ( "!=" | "==" ) comparison )* ;
-    fn parse_equality(&mut self) -> Result<Expression, ParserError> {
-        let mut expression = self.parse_comparison()?;
-        while self.check_and_consume_any(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let token = self.previous().clone();
-            let right = self.parse_comparison()?;
-            expression = Expression::binary(Box::new(expression), token, Box::new(right));
+    /// Parses a declaration, recording its error and skipping ahead to the
+    /// next statement if it is malformed.
+    fn parse_declaration(&mut self) -> Option<Statement> {
+        match self.try_parse_declaration() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                debug!("Encountered error: {}.", error);
+                self.errors.push(error);
+                self.synchronize();
+                None
+            },
         }
-        Ok(expression)
     }
 
-    /// comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
-    fn parse_comparison(&mut self) -> Result<Expression, ParserError> {
-        let mut expression = self.parse_term()?;
-        while self.check_and_consume_any(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let token = self.previous().clone();
-            let right = self.parse_term()?;
-            expression = Expression::binary(Box::new(expression), token, Box::new(right));
+    /// declaration → classDecl | funDecl | varDecl | statement ;
+    fn try_parse_declaration(&mut self) -> Result<Statement, ParserError> {
+        if self.check_and_consume(&TokenType::Class) {
+            return self.parse_class_declaration();
         }
-        Ok(expression)
+        if self.check_and_consume(&TokenType::Fun) {
+            let start = self.previous().span.start;
+            return Ok(self.parse_function(start)?.into());
+        }
+        if self.check_and_consume(&TokenType::Var) {
+            return self.parse_var_declaration();
+        }
+        self.parse_statement()
     }
 
-    /// term → factor ( ( "-" | "+" ) factor )* ;
-    fn parse_term(&mut self) -> Result<Expression, ParserError> {
-        let mut expression = self.parse_factor()?;
-        while self.check_and_consume_any(&[TokenType::Minus, TokenType::Plus]) {
-            let token = self.previous().clone();
-            let right = self.parse_factor()?;
-            expression = Expression::binary(Box::new(expression), token, Box::new(right));
+    /// classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+    fn parse_class_declaration(&mut self) -> Result<Statement, ParserError> {
+        let start = self.previous().span.start;
+        let name = self.consume_owned(&TokenType::Identifier)?;
+        let superclass = if self.check_and_consume(&TokenType::Less) {
+            Some(VariableExpr::new(NodeId::fresh(), self.consume_owned(&TokenType::Identifier)?))
+        } else {
+            None
+        };
+        self.consume(&TokenType::LeftBrace)?;
+        let mut methods = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            let start = self.peek().span.start;
+            methods.push(self.parse_function(start)?);
         }
-        Ok(expression)
+        self.consume(&TokenType::RightBrace)?;
+        Ok(Statement::class(name, superclass, methods, self.span_from(start)))
+    }
+
+    /// funDecl → "fun" function ;
+    /// function → IDENTIFIER "(" parameters? ")" block ;
+    /// parameters → IDENTIFIER ( "," IDENTIFIER )* ;
+    ///
+    /// `start` is where the declaration began, which is at `fun` unless it
+    /// is a method.
+    fn parse_function(&mut self, start: usize) -> Result<FunctionStmt, ParserError> {
+        let name = self.consume_owned(&TokenType::Identifier)?;
+        self.consume(&TokenType::LeftParen)?;
+        let mut params = vec![];
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let error = ParserError::TooManyParameters(self.peek_owned());
+                    self.errors.push(error);
+                }
+                params.push(self.consume_owned(&TokenType::Identifier)?);
+                if !self.check_and_consume(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(&TokenType::RightParen)?;
+        self.consume(&TokenType::LeftBrace)?;
+        let body = self.parse_block()?;
+        Ok(FunctionStmt::new(name, params, Rc::new(body), self.span_from(start)))
+    }
+
+    /// varDecl → "var" IDENTIFIER ( "=" expression )? ";" ;
+    fn parse_var_declaration(&mut self) -> Result<Statement, ParserError> {
+        let start = self.previous().span.start;
+        let name = self.consume_owned(&TokenType::Identifier)?;
+        let initializer = if self.check_and_consume(&TokenType::Equal) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        self.consume(&TokenType::Semicolon)?;
+        Ok(Statement::var(name, initializer, self.span_from(start)))
+    }
+
+    /// statement → exprStmt | forStmt | ifStmt | printStmt | whileStmt | block
+    /// ;
+    fn parse_statement(&mut self) -> Result<Statement, ParserError> {
+        if self.check_and_consume(&TokenType::For) {
+            return self.parse_for_statement();
+        }
+        if self.check_and_consume(&TokenType::If) {
+            return self.parse_if_statement();
+        }
+        if self.check_and_consume(&TokenType::Print) {
+            return self.parse_print_statement();
+        }
+        if self.check_and_consume(&TokenType::Return) {
+            return self.parse_return_statement();
+        }
+        if self.check_and_consume(&TokenType::While) {
+            return self.parse_while_statement();
+        }
+        if self.check_and_consume(&TokenType::LeftBrace) {
+            let start = self.previous().span.start;
+            let statements = self.parse_block()?;
+            return Ok(Statement::block(statements, self.span_from(start)));
+        }
+        self.parse_expression_statement()
     }
 
-    /// factor → unary ( ( "/" | "*" ) unary )* ;
-    fn parse_factor(&mut self) -> Result<Expression, ParserError> {
-        let mut expression = self.parse_unary()?;
-        while self.check_and_consume_any(&[TokenType::Slash, TokenType::Star]) {
-            let token = self.previous().clone();
-            let right = self.parse_unary()?;
-            expression = Expression::binary(Box::new(expression), token, Box::new(right));
+    /// forStmt → "for" "(" ( varDecl | exprStmt | ";" )
+    ///           expression? ";"
+    ///           expression? ")" statement ;
+    ///
+    /// There is no dedicated `for` node: the loop is desugared into an
+    /// equivalent `while` wrapped in blocks.
+    fn parse_for_statement(&mut self) -> Result<Statement, ParserError> {
+        let start = self.previous().span.start;
+        self.consume(&TokenType::LeftParen)?;
+        let initializer = if self.check_and_consume(&TokenType::Semicolon) {
+            None
+        } else if self.check_and_consume(&TokenType::Var) {
+            Some(self.parse_var_declaration()?)
+        } else {
+            Some(self.parse_expression_statement()?)
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            Expression::literal(LiteralValue::Boolean(true), Span::empty(self.peek().span.start))
+        } else {
+            self.parse_expression()?
+        };
+        self.consume(&TokenType::Semicolon)?;
+
+        let increment =
+            if self.check(&TokenType::RightParen) { None } else { Some(self.parse_expression()?) };
+        self.consume(&TokenType::RightParen)?;
+
+        let mut body = self.parse_statement()?;
+        let span = self.span_from(start);
+        if let Some(increment) = increment {
+            let increment_span = increment.span();
+            let block_span = body.span().merge(increment_span);
+            body = Statement::block(
+                vec![body, Statement::expression(increment, increment_span)],
+                block_span,
+            );
+        }
+        body = Statement::while_(condition, Box::new(body), span);
+        if let Some(initializer) = initializer {
+            body = Statement::block(vec![initializer, body], span);
         }
+        Ok(body)
+    }
+
+    /// ifStmt → "if" "(" expression ")" statement ( "else" statement )? ;
+    fn parse_if_statement(&mut self) -> Result<Statement, ParserError> {
+        let start = self.previous().span.start;
+        self.consume(&TokenType::LeftParen)?;
+        let condition = self.parse_expression()?;
+        self.consume(&TokenType::RightParen)?;
+
+        let then_branch = Box::new(self.parse_statement()?);
+        let else_branch = if self.check_and_consume(&TokenType::Else) {
+            Some(Box::new(self.parse_statement()?))
+        } else {
+            None
+        };
+        Ok(Statement::if_(condition, then_branch, else_branch, self.span_from(start)))
+    }
+
+    /// whileStmt → "while" "(" expression ")" statement ;
+    fn parse_while_statement(&mut self) -> Result<Statement, ParserError> {
+        let start = self.previous().span.start;
+        self.consume(&TokenType::LeftParen)?;
+        let condition = self.parse_expression()?;
+        self.consume(&TokenType::RightParen)?;
+        let body = Box::new(self.parse_statement()?);
+        Ok(Statement::while_(condition, body, self.span_from(start)))
+    }
+
+    /// block → "{" declaration* "}" ;
+    fn parse_block(&mut self) -> Result<Vec<Statement>, ParserError> {
+        let mut statements = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.extend(self.parse_declaration());
+        }
+        self.consume(&TokenType::RightBrace)?;
+        Ok(statements)
+    }
+
+    /// printStmt → "print" expression ";" ;
+    fn parse_print_statement(&mut self) -> Result<Statement, ParserError> {
+        let start = self.previous().span.start;
+        let expression = self.parse_expression()?;
+        self.consume(&TokenType::Semicolon)?;
+        Ok(Statement::print(expression, self.span_from(start)))
+    }
+
+    /// returnStmt → "return" expression? ";" ;
+    fn parse_return_statement(&mut self) -> Result<Statement, ParserError> {
+        let keyword = self.previous_owned();
+        let value =
+            if self.check(&TokenType::Semicolon) { None } else { Some(self.parse_expression()?) };
+        self.consume(&TokenType::Semicolon)?;
+        let span = self.span_from(keyword.span.start);
+        Ok(Statement::return_(keyword, value, span))
+    }
+
+    /// exprStmt → expression ";" ;
+    fn parse_expression_statement(&mut self) -> Result<Statement, ParserError> {
+        let start = self.peek().span.start;
+        let expression = self.parse_expression()?;
+        self.consume(&TokenType::Semicolon)?;
+        Ok(Statement::expression(expression, self.span_from(start)))
+    }
+
+    /// expression → comma ;
+    fn parse_expression(&mut self) -> Result<Expression, ParserError> {
+        self.parse_precedence(Precedence::Comma)
+    }
+
+    /// Parses an expression whose operators all bind at least as tightly as
+    /// `min`, as laid out by the rules in [`operators`].
+    fn parse_precedence(&mut self, min: Precedence) -> Result<Expression, ParserError> {
+        let mut expression = match lookup(&self.peek().token_type, Fixity::Prefix) {
+            Some(rule) => {
+                self.advance();
+                let operator = self.previous_owned();
+                let right = self.parse_precedence(rule.right_precedence())?;
+                Expression::unary(operator, Box::new(right))
+            },
+            None => self.parse_primary()?,
+        };
+        loop {
+            let token_type = &self.peek().token_type;
+            if let Some(rule) =
+                lookup(token_type, Fixity::Postfix).filter(|rule| rule.precedence >= min)
+            {
+                self.advance();
+                expression = self.parse_postfix(rule, expression)?;
+            } else if let Some(rule) =
+                lookup(token_type, Fixity::Infix).filter(|rule| rule.precedence >= min)
+            {
+                self.advance();
+                let operator = self.previous_owned();
+                expression = self.parse_infix(rule, expression, operator)?;
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    /// The operator token has been consumed.
+    fn parse_infix(
+        &mut self,
+        rule: Rule,
+        left: Expression,
+        operator: OwnedToken,
+    ) -> Result<Expression, ParserError> {
+        let left = Box::new(left);
+        if rule.operation == Operation::Conditional {
+            let then_branch = self.parse_expression()?;
+            self.consume(&TokenType::Colon)?;
+            let else_branch = self.parse_precedence(rule.right_precedence())?;
+            return Ok(Expression::conditional(left, Box::new(then_branch), Box::new(else_branch)));
+        }
+        let right = Box::new(self.parse_precedence(rule.right_precedence())?);
+        let expression = match rule.operation {
+            Operation::Logical => Expression::logical(left, operator, right),
+            Operation::Assign => match *left {
+                Expression::Variable(variable) => {
+                    Expression::assign(NodeId::fresh(), variable.name, right)
+                },
+                Expression::Get(get) => Expression::set(get.object, get.name, right),
+                // The parser is not confused, so report the error without
+                // unwinding.
+                left => {
+                    self.errors.push(ParserError::InvalidAssignmentTarget {
+                        equals: operator,
+                        target: left.span(),
+                    });
+                    left
+                },
+            },
+            _ => Expression::binary(left, operator, right),
+        };
         Ok(expression)
     }
 
-    /// unary → ( "!" | "-" ) unary | primary ;
-    fn parse_unary(&mut self) -> Result<Expression, ParserError> {
-        if self.check_and_consume_any(&[TokenType::Bang, TokenType::Minus]) {
-            let right = self.parse_unary()?;
-            return Ok(Expression::unary(self.previous().clone(), Box::new(right)));
+    /// The operator token has been consumed.
+    fn parse_postfix(&mut self, rule: Rule, left: Expression) -> Result<Expression, ParserError> {
+        match rule.operation {
+            Operation::Get => {
+                let name = self.consume_owned(&TokenType::Identifier)?;
+                Ok(Expression::get(Box::new(left), name))
+            },
+            _ => self.finish_call(left),
         }
-        self.parse_primary()
     }
 
-    /// primary → NUMBER | STRING | "true" | "false" | "nil"
-    ///           | "(" expression ")" ;
+    /// arguments → assignment ( "," assignment )* ;
+    ///
+    /// Arguments stop short of the comma operator, which would otherwise take
+    /// the commas between them.
+    fn finish_call(&mut self, callee: Expression) -> Result<Expression, ParserError> {
+        let mut arguments = vec![];
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let error = ParserError::TooManyArguments(self.peek_owned());
+                    self.errors.push(error);
+                }
+                arguments.push(self.parse_precedence(Precedence::Assignment)?);
+                if !self.check_and_consume(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume_owned(&TokenType::RightParen)?;
+        Ok(Expression::call(Box::new(callee), paren, arguments))
+    }
+
+    /// primary → NUMBER | STRING | "true" | "false" | "nil" | "this"
+    ///           | "(" expression ")" | IDENTIFIER | "super" "." IDENTIFIER ;
+    ///
+    /// As an error production, a binary operator and its right operand stand
+    /// in for a primary, and are reported as missing the left operand.
     fn parse_primary(&mut self) -> Result<Expression, ParserError> {
         if self.check_and_consume(&TokenType::False) {
-            return Ok(Expression::literal(LiteralValue::Boolean(false)));
+            return Ok(Expression::literal(LiteralValue::Boolean(false), self.previous().span));
         }
         if self.check_and_consume(&TokenType::True) {
-            return Ok(Expression::literal(LiteralValue::Boolean(true)));
+            return Ok(Expression::literal(LiteralValue::Boolean(true), self.previous().span));
         }
         if self.check_and_consume(&TokenType::Nil) {
-            return Ok(Expression::literal(LiteralValue::Nil));
+            return Ok(Expression::literal(LiteralValue::Nil, self.previous().span));
         }
 
         if self.check_and_consume_any(&[TokenType::Number, TokenType::String]) {
-            return Ok(Expression::literal(self.previous().literal.clone().unwrap()));
+            let token = self.previous.as_ref().expect("no token has been consumed yet");
+            let value = token.literal.as_ref().unwrap().intern(self.interner);
+            return Ok(Expression::literal(value, token.span));
+        }
+
+        if self.check_and_consume(&TokenType::StringStart) {
+            return self.finish_interpolation();
+        }
+
+        if self.check_and_consume(&TokenType::This) {
+            return Ok(Expression::this(NodeId::fresh(), self.previous_owned()));
+        }
+
+        if self.check_and_consume(&TokenType::Super) {
+            let keyword = self.previous_owned();
+            self.consume(&TokenType::Dot)?;
+            let method = self.consume_owned(&TokenType::Identifier)?;
+            return Ok(Expression::super_(NodeId::fresh(), keyword, method));
+        }
+
+        if self.check_and_consume(&TokenType::Identifier) {
+            return Ok(Expression::variable(NodeId::fresh(), self.previous_owned()));
         }
 
         if self.check_and_consume(&TokenType::LeftParen) {
+            let start = self.previous().span;
             let expression = self.parse_expression()?;
-            self.consume(&TokenType::RightParen);
-            return Ok(Expression::grouping(Box::new(expression)));
+            let end = self.consume(&TokenType::RightParen)?.span;
+            return Ok(Expression::grouping(Box::new(expression), start.merge(end)));
+        }
+
+        // A binary operator with its left operand missing: skip over the
+        // right operand, so that parsing picks up after it.
+        let binary = lookup(&self.peek().token_type, Fixity::Infix)
+            .filter(|rule| rule.precedence > Precedence::Conditional);
+        if let Some(rule) = binary {
+            self.advance();
+            let error = ParserError::MissingLeftOperand(self.previous_owned());
+            if let Err(right_error) = self.parse_precedence(rule.right_precedence()) {
+                self.errors.push(error);
+                return Err(right_error);
+            }
+            return Err(error);
         }
 
-        Err(ParserError::UnexpectedToken(self.peek().clone()))
+        Err(ParserError::UnexpectedToken(self.peek_owned()))
+    }
+
+    /// interpolation → STRING_START expression ( STRING_MIDDLE expression )*
+    /// STRING_END ;
+    fn finish_interpolation(&mut self) -> Result<Expression, ParserError> {
+        let mut strings = vec![self.previous_owned()];
+        let mut expressions = vec![];
+        loop {
+            expressions.push(self.parse_expression()?);
+            if self.check_and_consume(&TokenType::StringMiddle) {
+                strings.push(self.previous_owned());
+            } else {
+                strings.push(self.consume_owned(&TokenType::StringEnd)?);
+                return Ok(Expression::interpolation(strings, expressions));
+            }
+        }
     }
 }
 
-impl Parser {
+impl<'src, I: TokenStream<'src>> Parser<'src, '_, I> {
     fn check_and_consume_any(&mut self, token_types: &[TokenType]) -> bool {
         for token_type in token_types {
             if self.check(token_type) {
@@ -133,11 +523,43 @@ impl Parser {
         false
     }
 
-    fn consume(&mut self, token_type: &TokenType) -> &Token {
+    /// Like [`Parser::consume`], keeping the token for the AST.
+    fn consume_owned(&mut self, token_type: &TokenType) -> Result<OwnedToken, ParserError> {
+        self.consume(token_type)?;
+        Ok(self.previous_owned())
+    }
+
+    fn consume(&mut self, token_type: &TokenType) -> Result<&Token<'src>, ParserError> {
         if self.check(token_type) {
-            return self.advance();
+            return Ok(self.advance());
+        }
+        Err(ParserError::Expected {
+            expected: token_type.clone(),
+            found: self.peek_owned(),
+        })
+    }
+
+    /// Discards tokens until the start of the next statement, so that one
+    /// syntax error does not cascade into many.
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {},
+            }
+            self.advance();
         }
-        panic!("Expected token of type {:?}, but got {:?}.", token_type, self.peek());
     }
 
     fn check(&self, token_type: &TokenType) -> bool {
@@ -147,48 +569,196 @@ impl Parser {
         self.peek().token_type == *token_type
     }
 
-    fn advance(&mut self) -> &Token {
+    fn advance(&mut self) -> &Token<'src> {
         if !self.is_at_end() {
-            self.current += 1;
+            self.previous = self.lookahead.pop_front();
+            self.fill(1);
         }
         self.previous()
     }
 
+    /// Pulls from the stream until `count` tokens are buffered. Scanner
+    /// errors met on the way are recorded in order, and a stream that ends
+    /// without `Eof` is padded with it.
+    fn fill(&mut self, count: usize) {
+        while self.lookahead.len() < count {
+            match self.tokens.next() {
+                Some(Ok(token)) => self.lookahead.push_back(token),
+                Some(Err(error)) => self.errors.push(ParserError::Scanner(error)),
+                None => {
+                    let last = self.lookahead.back().or(self.previous.as_ref());
+                    let eof = match last {
+                        Some(last) => {
+                            Token::eof(last.line, last.character + 1, Span::empty(last.span.end))
+                        },
+                        None => Token::eof(1, 1, Span::empty(0)),
+                    };
+                    self.lookahead.push_back(eof);
+                },
+            }
+        }
+    }
+
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
     }
 
-    fn peek(&self) -> &Token {
-        &self.tokens[self.current]
+    fn peek(&self) -> &Token<'src> {
+        &self.lookahead[0]
+    }
+
+    fn previous(&self) -> &Token<'src> {
+        self.previous.as_ref().expect("no token has been consumed yet")
+    }
+
+    fn peek_owned(&mut self) -> OwnedToken {
+        self.lookahead[0].to_owned_token(self.interner)
     }
 
-    fn previous(&self) -> &Token {
-        &self.tokens[self.current - 1]
+    fn previous_owned(&mut self) -> OwnedToken {
+        let previous = self.previous.as_ref().expect("no token has been consumed yet");
+        previous.to_owned_token(self.interner)
+    }
+
+    /// From `start` through the end of the last consumed token.
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.previous().span.end)
     }
 }
 
 #[derive(Debug)]
 pub enum ParserError {
-    UnexpectedToken(Token),
+    UnexpectedToken(OwnedToken),
+    Expected {
+        expected: TokenType,
+        found: OwnedToken,
+    },
+    InvalidAssignmentTarget {
+        equals: OwnedToken,
+        target: Span,
+    },
+    TooManyArguments(OwnedToken),
+    TooManyParameters(OwnedToken),
+    /// A binary operator where an expression should start.
+    MissingLeftOperand(OwnedToken),
+    /// A scanner error passed through from the token stream.
+    Scanner(ScannerError),
+}
+
+impl ParserError {
+    /// The token the parser was looking at when it gave up. Scanner errors
+    /// happen between tokens and have none.
+    pub fn token(&self) -> Option<&OwnedToken> {
+        match self {
+            ParserError::UnexpectedToken(token)
+            | ParserError::Expected {
+                found: token, ..
+            }
+            | ParserError::InvalidAssignmentTarget {
+                equals: token, ..
+            }
+            | ParserError::TooManyArguments(token)
+            | ParserError::TooManyParameters(token)
+            | ParserError::MissingLeftOperand(token) => Some(token),
+            ParserError::Scanner(_) => None,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ParserError::UnexpectedToken(_) => "Expect expression.".to_string(),
+            ParserError::Expected {
+                expected, ..
+            } => format!("Expect {}.", expected),
+            ParserError::InvalidAssignmentTarget {
+                ..
+            } => "Invalid assignment target.".to_string(),
+            ParserError::TooManyArguments(_) => {
+                format!("Can't have more than {} arguments.", MAX_ARGUMENTS)
+            },
+            ParserError::TooManyParameters(_) => {
+                format!("Can't have more than {} parameters.", MAX_ARGUMENTS)
+            },
+            ParserError::MissingLeftOperand(operator) => {
+                format!("Binary operator '{}' requires a left-hand operand.", operator.lexeme)
+            },
+            ParserError::Scanner(error) => error.message(),
+        }
+    }
 }
 
 impl Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ParserError::UnexpectedToken(token) => write!(
-                f,
-                "Unexpected token: {:?} at line {:?}:{:?}",
-                &token.token_type, &token.line, &token.character
-            ),
+        if let ParserError::Scanner(error) = self {
+            return write!(f, "{}", error);
+        }
+        match self.token() {
+            None => write!(f, "{}", self.message()),
+            Some(token) if token.token_type == TokenType::Eof => {
+                write!(f, "[line {}] Error at end: {}", token.line, self.message())
+            },
+            Some(token) => {
+                write!(f, "[line {}] Error at '{}': {}", token.line, token.lexeme, self.message())
+            },
         }
     }
 }
 
+impl From<&ParserError> for Diagnostic {
+    fn from(error: &ParserError) -> Self {
+        let diagnostic = Diagnostic::error(error.message());
+        match error {
+            ParserError::UnexpectedToken(token) => diagnostic
+                .with_code("E0101")
+                .with_primary(token.span, format!("expected expression, found {}", found(token))),
+            ParserError::Expected {
+                expected,
+                found: token,
+            } => diagnostic
+                .with_code("E0102")
+                .with_primary(token.span, format!("expected {}, found {}", expected, found(token))),
+            ParserError::InvalidAssignmentTarget {
+                equals,
+                target,
+            } => diagnostic
+                .with_code("E0103")
+                .with_primary(*target, "cannot assign to this")
+                .with_secondary(equals.span, "assignment requested here")
+                .with_help("only variables and fields can be assigned to"),
+            ParserError::TooManyArguments(token) | ParserError::TooManyParameters(token) => {
+                diagnostic.with_code("E0104").with_primary(token.span, "limit exceeded here")
+            },
+            ParserError::MissingLeftOperand(operator) => diagnostic
+                .with_code("E0105")
+                .with_primary(operator.span, "expected an operand before this")
+                .with_help(format!("add an expression before '{}'", operator.lexeme)),
+            ParserError::Scanner(error) => Diagnostic::from(error),
+        }
+    }
+}
+
+/// How the token the parser stumbled on is described in labels.
+fn found(token: &OwnedToken) -> String {
+    match token.token_type {
+        TokenType::Eof => token.token_type.to_string(),
+        _ => format!("'{}'", token.lexeme),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use log::LevelFilter::Trace;
 
-    use crate::parser::ast::PrettyPrinter;
+    use crate::{
+        diagnostic::Diagnostic,
+        interner::Interner,
+        parser::{
+            ParserError,
+            ast::{PrettyPrinter, Statement},
+        },
+        scanner::{Scanner, TokenType},
+        span::{Span, Spanned},
+    };
 
     fn init_logger() {
         let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
@@ -197,15 +767,343 @@ mod tests {
     #[test]
     fn parse_expression() {
         init_logger();
-        let tokens = crate::scanner::scan(
+        let mut interner = Interner::new();
+        let tokens = Scanner::new(
             "
             1 + 2 * (3 - 4) / 5 * 6;
             ",
-        )
-        .collect::<Vec<_>>();
-        let mut parser = crate::parser::Parser::new(tokens);
+        );
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
         let result = parser.parse_expression().expect("Parsing failed.");
-        let actual = result.accept(&PrettyPrinter::clear()).expect("Pretty printing failed.");
+        let actual =
+            result.accept(&PrettyPrinter::clear(&interner)).expect("Pretty printing failed.");
         assert_eq!(actual, "(1 + (((2 * ((3 - 4))) / 5) * 6))");
     }
+
+    #[test]
+    fn parse_precedence_and_associativity() {
+        init_logger();
+        let mut interner = Interner::new();
+        let cases = [
+            ("1 - 2 - 3", "((1 - 2) - 3)"),
+            ("a = b = c", "(a = (b = c))"),
+            ("a or b and c or d", "((a or (b and c)) or d)"),
+            ("1 < 2 == 3 >= 4", "((1 < 2) == (3 >= 4))"),
+            ("-a.b(c) * !!d", "((-a.b(c)) * (!(!d)))"),
+            ("a.b = c + 1", "(a.b = (c + 1))"),
+            ("f(1)(2).g", "f(1)(2).g"),
+            ("a ? b : c ? d : e", "(a ? b : (c ? d : e))"),
+            ("a or b ? c : d", "((a or b) ? c : d)"),
+            ("a = b ? c : d", "(a = (b ? c : d))"),
+            ("a ? b, c : d", "(a ? (b, c) : d)"),
+            ("a, b = c, d", "((a, (b = c)), d)"),
+            ("f(a, b ? c : d)", "f(a, (b ? c : d))"),
+            ("f((a, b))", "f(((a, b)))"),
+        ];
+        for (source, expected) in cases {
+            let mut parser = crate::parser::Parser::new(Scanner::new(source), &mut interner);
+            let expression = parser.parse().expect("Parsing failed.");
+            let actual = expression.accept(&PrettyPrinter::clear(&interner)).unwrap();
+            assert_eq!(actual, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn parse_program() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new(
+            "
+            print \"Hello, world!\";
+            1 + 2;
+            print (1 + 2) * 3;
+            ",
+        );
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program().into_result().expect("Parsing failed.");
+        let actual = PrettyPrinter::new(&interner).print_program(&result);
+        assert_eq!(actual, "print \"Hello, world!\";\n1 + 2;\nprint (1 + 2) * 3;");
+    }
+
+    #[test]
+    fn parse_variables() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new("var a; var b = 1; a = b = 2 + b;");
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program().into_result().expect("Parsing failed.");
+        let actual = PrettyPrinter::clear(&interner).print_program(&result);
+        assert_eq!(actual, "var a;\nvar b = 1;\n(a = (b = (2 + b)));");
+    }
+
+    #[test]
+    fn parse_control_flow() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new(
+            "
+            if (a or b and c) print 1; else { print 2; }
+            while (i < 10) i = i + 1;
+            ",
+        );
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program().into_result().expect("Parsing failed.");
+        let actual = PrettyPrinter::clear(&interner).print_program(&result);
+        assert_eq!(
+            actual,
+            "if ((a or (b and c))) print 1; else { print 2; }\nwhile ((i < 10)) (i = (i + 1));"
+        );
+    }
+
+    #[test]
+    fn parse_for_desugars_to_while() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new("for (var i = 0; i < 3; i = i + 1) print i; for (;;) {}");
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program().into_result().expect("Parsing failed.");
+        let actual = PrettyPrinter::new(&interner).print_program(&result);
+        assert_eq!(actual, "{ var i = 0; while (i < 3) { print i; i = i + 1; } }\nwhile (true) {}");
+    }
+
+    #[test]
+    fn parse_functions() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new(
+            "
+            fun add(a, b) { return a + b; }
+            fun noop() { return; }
+            print add(1, 2)(3)();
+            ",
+        );
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program().into_result().expect("Parsing failed.");
+        let actual = PrettyPrinter::new(&interner).print_program(&result);
+        assert_eq!(
+            actual,
+            "fun add(a, b) { return a + b; }\nfun noop() { return; }\nprint add(1, 2)(3)();"
+        );
+    }
+
+    #[test]
+    fn parse_classes() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new(
+            "
+            class Empty {}
+            class Point < Base {
+                init(x) { this.x = x; }
+                sum() { return super.sum() + this.x; }
+            }
+            a.b.c = d.e(f).g;
+            ",
+        );
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program().into_result().expect("Parsing failed.");
+        let actual = PrettyPrinter::new(&interner).print_program(&result);
+        assert_eq!(
+            actual,
+            "class Empty {}\n\
+             class Point < Base { init(x) { this.x = x; } sum() { return super.sum() + this.x; } }\n\
+             a.b.c = d.e(f).g;"
+        );
+    }
+
+    #[test]
+    fn parse_too_many_arguments() {
+        init_logger();
+        let mut interner = Interner::new();
+        let source = format!("f({});", vec!["1"; 256].join(", "));
+        let tokens = Scanner::new(&source);
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program();
+        assert!(matches!(result.errors[..], [crate::parser::ParserError::TooManyArguments(_)]));
+        assert_eq!(result.statements.len(), 1);
+    }
+
+    #[test]
+    fn parse_too_many_parameters() {
+        init_logger();
+        let mut interner = Interner::new();
+        let params: Vec<_> = (0..256).map(|i| format!("p{}", i)).collect();
+        let source = format!("fun f({}) {{}}", params.join(", "));
+        let result =
+            crate::parser::Parser::new(Scanner::new(&source), &mut interner).parse_program();
+        let [error] = &result.errors[..] else { panic!("{:?}", result.errors) };
+        assert!(matches!(error, crate::parser::ParserError::TooManyParameters(_)));
+        assert_eq!(error.message(), "Can't have more than 255 parameters.");
+        assert_eq!(result.statements.len(), 1);
+    }
+
+    #[test]
+    fn parse_keeps_statements_around_errors() {
+        init_logger();
+        let mut interner = Interner::new();
+        let source = "fun f() { print 1; var = 2; print 3; } { print 4 5; print 6; } print 7;";
+        let result =
+            crate::parser::Parser::new(Scanner::new(source), &mut interner).parse_program();
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(
+            PrettyPrinter::new(&interner).print_program(&result.statements),
+            "fun f() { print 1; print 3; }\n{ print 6; }\nprint 7;"
+        );
+    }
+
+    #[test]
+    fn parse_invalid_assignment_target() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new("a + b = c;");
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program();
+        assert!(matches!(result.errors[..], [
+            crate::parser::ParserError::InvalidAssignmentTarget { .. }
+        ]));
+    }
+
+    #[test]
+    fn parse_recovers_at_statement_boundaries() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new(
+            "
+            print (1 + 2;
+            var a = 1;
+            var = 2;
+            print a print a;
+            fun f() { print a; }
+            print a;
+            ",
+        );
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program();
+        assert!(matches!(result.errors[..], [
+            ParserError::Expected {
+                expected: TokenType::RightParen,
+                ..
+            },
+            ParserError::Expected {
+                expected: TokenType::Identifier,
+                ..
+            },
+            ParserError::Expected {
+                expected: TokenType::Semicolon,
+                ..
+            },
+        ]));
+        let actual = PrettyPrinter::new(&interner).print_program(&result.statements);
+        assert_eq!(actual, "var a = 1;\nfun f() { print a; }\nprint a;");
+    }
+
+    #[test]
+    fn parse_missing_expression() {
+        init_logger();
+        let mut interner = Interner::new();
+        let tokens = Scanner::new("print ;");
+        let mut parser = crate::parser::Parser::new(tokens, &mut interner);
+        let result = parser.parse_program();
+        assert!(matches!(result.errors[..], [ParserError::UnexpectedToken(_)]));
+        assert!(result.statements.is_empty());
+    }
+
+    #[test]
+    fn parse_conditional_without_else() {
+        init_logger();
+        let mut interner = Interner::new();
+        let mut parser = crate::parser::Parser::new(Scanner::new("print a ? b;"), &mut interner);
+        let result = parser.parse_program();
+        assert!(matches!(&result.errors[..], [ParserError::Expected {
+            expected: TokenType::Colon,
+            ..
+        }]));
+    }
+
+    #[test]
+    fn parse_missing_left_operand() {
+        init_logger();
+        let mut interner = Interner::new();
+        let source = "print * 3;\nprint (== x) + 1;\nprint a or;\nprint and;";
+        let mut parser = crate::parser::Parser::new(Scanner::new(source), &mut interner);
+        let result = parser.parse_program();
+        let messages: Vec<_> = result.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, [
+            "[line 1] Error at '*': Binary operator '*' requires a left-hand operand.",
+            "[line 2] Error at '==': Binary operator '==' requires a left-hand operand.",
+            "[line 3] Error at ';': Expect expression.",
+            "[line 4] Error at 'and': Binary operator 'and' requires a left-hand operand.",
+            "[line 4] Error at ';': Expect expression.",
+        ]);
+        assert!(result.statements.is_empty());
+
+        let diagnostic = Diagnostic::from(&result.errors[0]);
+        assert_eq!(diagnostic.code, Some("E0105"));
+        assert_eq!(diagnostic.primary_span(), Some(Span::new(6, 7)));
+    }
+
+    #[test]
+    fn parse_error_diagnostics() {
+        init_logger();
+        let mut interner = Interner::new();
+        let source = "print ;\n1 + 2 = 3;\nprint 1";
+        let tokens = Scanner::new(source);
+        let result = crate::parser::Parser::new(tokens, &mut interner).parse_program();
+        let messages: Vec<_> = result.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, vec![
+            "[line 1] Error at ';': Expect expression.",
+            "[line 2] Error at '=': Invalid assignment target.",
+            "[line 3] Error at end: Expect ';'.",
+        ]);
+
+        let diagnostics: Vec<Diagnostic> = result.errors.iter().map(Diagnostic::from).collect();
+        assert_eq!(diagnostics[0].code, Some("E0101"));
+        assert_eq!(diagnostics[1].primary_span(), Some(Span::new(8, 13)));
+        assert_eq!(diagnostics[2].primary_span(), Some(Span::empty(source.len())));
+    }
+
+    #[test]
+    fn parse_from_stream() {
+        init_logger();
+        let mut interner = Interner::new();
+        let mut scanner = Scanner::new("1 + 2; print 3;");
+        let expression = crate::parser::Parser::new(&mut scanner, &mut interner).parse().unwrap();
+        assert_eq!(expression.accept(&PrettyPrinter::new(&interner)).unwrap(), "1 + 2");
+        // Only the one token of lookahead past the expression was pulled.
+        assert_eq!(scanner.next().unwrap().unwrap().token_type, TokenType::Print);
+    }
+
+    #[test]
+    fn parse_reports_scanner_errors_in_order() {
+        init_logger();
+        let mut interner = Interner::new();
+        let result = crate::parser::Parser::new(Scanner::new("print 1 @;\nprint ;"), &mut interner)
+            .parse_program();
+        let messages: Vec<_> = result.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, vec![
+            "[line 1] Error: Unexpected character '@'.",
+            "[line 2] Error at ';': Expect expression.",
+        ]);
+        assert_eq!(result.statements.len(), 1);
+    }
+
+    #[test]
+    fn parse_interpolation() {
+        init_logger();
+        let mut interner = Interner::new();
+        let source = r#"print "Hello ${name}, you are ${age + 1}\t${"n${e}sted"}";"#;
+        let result =
+            crate::parser::Parser::new(Scanner::new(source), &mut interner).parse_program();
+        assert!(result.errors.is_empty());
+        let actual = PrettyPrinter::new(&interner).print_program(&result.statements);
+        assert_eq!(actual, source);
+        assert_eq!(result.statements[0].span(), Span::new(0, source.len()));
+        let Statement::Print(print) = &result.statements[0] else { panic!() };
+        assert_eq!(print.expression.span(), Span::new(6, source.len() - 1));
+
+        let result = crate::parser::Parser::new(Scanner::new("print \"a ${b c}\";"), &mut interner)
+            .parse_program();
+        let messages: Vec<_> = result.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, vec!["[line 1] Error at 'c': Expect '}'."]);
+    }
 }
@@ -0,0 +1,151 @@
+use crate::scanner::TokenType;
+
+/// How tightly an operator holds on to its operands, loosest first.
+///
+/// ```text
+/// comma       → assignment ( "," assignment )* ;
+/// assignment  → ( call "." )? IDENTIFIER "=" assignment | conditional ;
+/// conditional → logic_or ( "?" expression ":" conditional )? ;
+/// logic_or    → logic_and ( "or" logic_and )* ;
+/// logic_and   → equality ( "and" equality )* ;
+/// equality    → comparison ( ( "!=" | "==" ) comparison )* ;
+/// comparison  → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+/// term        → factor ( ( "-" | "+" ) factor )* ;
+/// factor      → unary ( ( "/" | "*" ) unary )* ;
+/// unary       → ( "!" | "-" ) unary | call ;
+/// call        → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    Comma,
+    Assignment,
+    Conditional,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+}
+
+impl Precedence {
+    /// The next tighter level, or `self` for the tightest.
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Comma => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary | Precedence::Call => Precedence::Call,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// The node an operator builds from its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Unary,
+    Binary,
+    /// Like `Binary`, but the right operand is only evaluated when needed.
+    Logical,
+    Assign,
+    /// `?`, followed by the branches.
+    Conditional,
+    Call,
+    Get,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub operation: Operation,
+    pub precedence: Precedence,
+    pub associativity: Associativity,
+}
+
+impl Rule {
+    const fn new(
+        operation: Operation,
+        precedence: Precedence,
+        associativity: Associativity,
+    ) -> Self {
+        Rule {
+            operation,
+            precedence,
+            associativity,
+        }
+    }
+
+    /// The loosest precedence allowed in the operand to the right of the
+    /// operator. Left-associative operators stop it at their own level, so
+    /// that `a - b - c` groups as `(a - b) - c`.
+    pub fn right_precedence(&self) -> Precedence {
+        match self.associativity {
+            Associativity::Left => self.precedence.next(),
+            Associativity::Right => self.precedence,
+        }
+    }
+}
+
+/// Where an operator goes relative to its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixity {
+    /// Before its operand, which binds as tightly as the operator's
+    /// precedence.
+    Prefix,
+    /// Between two operands.
+    Infix,
+    /// After its operand, which it takes as it is.
+    Postfix,
+}
+
+/// Every operator the parser knows, loosest first. A token may appear once
+/// for each fixity, as `-` does for negation and subtraction.
+static OPERATORS: &[(TokenType, Fixity, Rule)] = {
+    use Associativity::*;
+    use Fixity::*;
+    use Operation::*;
+    use Precedence as P;
+
+    &[
+        (TokenType::Comma, Infix, Rule::new(Binary, P::Comma, Left)),
+        (TokenType::Equal, Infix, Rule::new(Assign, P::Assignment, Right)),
+        (TokenType::Question, Infix, Rule::new(Conditional, P::Conditional, Right)),
+        (TokenType::Or, Infix, Rule::new(Logical, P::Or, Left)),
+        (TokenType::And, Infix, Rule::new(Logical, P::And, Left)),
+        (TokenType::BangEqual, Infix, Rule::new(Binary, P::Equality, Left)),
+        (TokenType::EqualEqual, Infix, Rule::new(Binary, P::Equality, Left)),
+        (TokenType::Greater, Infix, Rule::new(Binary, P::Comparison, Left)),
+        (TokenType::GreaterEqual, Infix, Rule::new(Binary, P::Comparison, Left)),
+        (TokenType::Less, Infix, Rule::new(Binary, P::Comparison, Left)),
+        (TokenType::LessEqual, Infix, Rule::new(Binary, P::Comparison, Left)),
+        (TokenType::Minus, Infix, Rule::new(Binary, P::Term, Left)),
+        (TokenType::Plus, Infix, Rule::new(Binary, P::Term, Left)),
+        (TokenType::Slash, Infix, Rule::new(Binary, P::Factor, Left)),
+        (TokenType::Star, Infix, Rule::new(Binary, P::Factor, Left)),
+        (TokenType::Bang, Prefix, Rule::new(Unary, P::Unary, Right)),
+        (TokenType::Minus, Prefix, Rule::new(Unary, P::Unary, Right)),
+        (TokenType::LeftParen, Postfix, Rule::new(Call, P::Call, Left)),
+        (TokenType::Dot, Postfix, Rule::new(Get, P::Call, Left)),
+    ]
+};
+
+/// The rule for `token_type` used as a `fixity` operator, if it is one.
+pub fn lookup(token_type: &TokenType, fixity: Fixity) -> Option<Rule> {
+    OPERATORS
+        .iter()
+        .find(|(operator, f, _)| operator == token_type && *f == fixity)
+        .map(|(_, _, rule)| *rule)
+}
@@ -0,0 +1,250 @@
+use crate::{
+    interner::Interner,
+    parser::ast::*,
+    scanner::{LiteralValue, OwnedToken},
+};
+
+/// Prints the AST as Lisp-style S-expressions, with the operator first and
+/// every node in its own parentheses, as in `(* (- 123) (group 45.67))`.
+/// Statements print the same way, led by their keyword.
+pub struct SexprPrinter<'a> {
+    /// Resolves string literals, which are interned by the parser.
+    interner: &'a Interner,
+}
+
+impl<'a> SexprPrinter<'a> {
+    pub fn new(interner: &'a Interner) -> Self {
+        SexprPrinter {
+            interner,
+        }
+    }
+
+    pub fn print_program(&self, statements: &[Statement]) -> String {
+        let lines = statements.iter().filter_map(|s| s.accept(self).ok()).collect::<Vec<_>>();
+        lines.join("\n")
+    }
+
+    /// `(name part...)`, leaving out the space when there are no parts.
+    fn list<'e>(
+        &self,
+        name: &str,
+        parts: impl IntoIterator<Item = &'e Expression>,
+    ) -> Result<String, ()> {
+        let mut result = format!("({}", name);
+        for part in parts {
+            result.push(' ');
+            result.push_str(&part.accept(self)?);
+        }
+        result.push(')');
+        Ok(result)
+    }
+
+    fn statements(&self, statements: &[Statement]) -> Result<String, ()> {
+        statements.iter().map(|s| Ok(format!(" {}", s.accept(self)?))).collect()
+    }
+
+    fn function(&self, stmt: &FunctionStmt) -> Result<String, ()> {
+        let params = stmt.params.iter().map(|p| p.lexeme.as_str()).collect::<Vec<_>>();
+        let body = self.statements(&stmt.body)?;
+        Ok(format!("(fun {} ({}){})", stmt.name.lexeme, params.join(" "), body))
+    }
+}
+
+fn operator(token: &OwnedToken) -> &str {
+    &token.lexeme
+}
+
+impl ExpressionVisitor<String, ()> for SexprPrinter<'_> {
+    fn visit_assign(&self, expr: &AssignExpr) -> Result<String, ()> {
+        Ok(format!("(= {} {})", expr.name.lexeme, expr.value.accept(self)?))
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> Result<String, ()> {
+        self.list(operator(&expr.operator), [&*expr.left, &*expr.right])
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> Result<String, ()> {
+        self.list("call", std::iter::once(&*expr.callee).chain(&expr.arguments))
+    }
+
+    fn visit_conditional(&self, expr: &ConditionalExpr) -> Result<String, ()> {
+        self.list("?", [&*expr.condition, &*expr.then_branch, &*expr.else_branch])
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> Result<String, ()> {
+        Ok(format!("(. {} {})", expr.object.accept(self)?, expr.name.lexeme))
+    }
+
+    fn visit_grouping(&self, expr: &GroupingExpr) -> Result<String, ()> {
+        self.list("group", [&*expr.expression])
+    }
+
+    /// The string parts print as the text they stand for, between the
+    /// expressions spliced into them.
+    fn visit_interpolation(&self, expr: &InterpolationExpr) -> Result<String, ()> {
+        let mut result = "(str".to_string();
+        for (index, string) in expr.strings.iter().enumerate() {
+            if let Some(LiteralValue::String(text)) = string.literal
+                && !self.interner.resolve(text).is_empty()
+            {
+                result.push_str(&format!(" {:?}", self.interner.resolve(text)));
+            }
+            if let Some(expression) = expr.expressions.get(index) {
+                result.push_str(&format!(" {}", expression.accept(self)?));
+            }
+        }
+        result.push(')');
+        Ok(result)
+    }
+
+    fn visit_literal(&self, expr: &LiteralExpr) -> Result<String, ()> {
+        let value = match expr.value {
+            LiteralValue::String(s) => format!("{:?}", self.interner.resolve(s)),
+            LiteralValue::Number(n) => format!("{}", n),
+            LiteralValue::Boolean(b) => format!("{}", b),
+            LiteralValue::Nil => "nil".to_string(),
+        };
+        Ok(value)
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> Result<String, ()> {
+        self.list(operator(&expr.operator), [&*expr.left, &*expr.right])
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> Result<String, ()> {
+        let object = expr.object.accept(self)?;
+        Ok(format!("(= (. {} {}) {})", object, expr.name.lexeme, expr.value.accept(self)?))
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> Result<String, ()> {
+        Ok(format!("(. super {})", expr.method.lexeme))
+    }
+
+    fn visit_this(&self, _expr: &ThisExpr) -> Result<String, ()> {
+        Ok("this".to_string())
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> Result<String, ()> {
+        self.list(operator(&expr.operator), [&*expr.right])
+    }
+
+    fn visit_variable(&self, expr: &VariableExpr) -> Result<String, ()> {
+        Ok(expr.name.lexeme.to_string())
+    }
+}
+
+impl StatementVisitor<String, ()> for SexprPrinter<'_> {
+    fn visit_block(&self, stmt: &BlockStmt) -> Result<String, ()> {
+        Ok(format!("(block{})", self.statements(&stmt.statements)?))
+    }
+
+    fn visit_class(&self, stmt: &ClassStmt) -> Result<String, ()> {
+        let mut result = format!("(class {}", stmt.name.lexeme);
+        if let Some(superclass) = &stmt.superclass {
+            result.push_str(&format!(" (< {})", superclass.name.lexeme));
+        }
+        for method in &stmt.methods {
+            result.push_str(&format!(" {}", self.function(method)?));
+        }
+        result.push(')');
+        Ok(result)
+    }
+
+    fn visit_expression(&self, stmt: &ExpressionStmt) -> Result<String, ()> {
+        self.list(";", [&stmt.expression])
+    }
+
+    fn visit_function(&self, stmt: &FunctionStmt) -> Result<String, ()> {
+        self.function(stmt)
+    }
+
+    fn visit_if(&self, stmt: &IfStmt) -> Result<String, ()> {
+        let condition = stmt.condition.accept(self)?;
+        let then_branch = stmt.then_branch.accept(self)?;
+        match &stmt.else_branch {
+            Some(else_branch) => {
+                Ok(format!("(if {} {} {})", condition, then_branch, else_branch.accept(self)?))
+            },
+            None => Ok(format!("(if {} {})", condition, then_branch)),
+        }
+    }
+
+    fn visit_print(&self, stmt: &PrintStmt) -> Result<String, ()> {
+        self.list("print", [&stmt.expression])
+    }
+
+    fn visit_return(&self, stmt: &ReturnStmt) -> Result<String, ()> {
+        self.list("return", &stmt.value)
+    }
+
+    fn visit_var(&self, stmt: &VarStmt) -> Result<String, ()> {
+        match &stmt.initializer {
+            Some(initializer) => {
+                Ok(format!("(var {} {})", stmt.name.lexeme, initializer.accept(self)?))
+            },
+            None => Ok(format!("(var {})", stmt.name.lexeme)),
+        }
+    }
+
+    fn visit_while(&self, stmt: &WhileStmt) -> Result<String, ()> {
+        let condition = stmt.condition.accept(self)?;
+        Ok(format!("(while {} {})", condition, stmt.body.accept(self)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter::Trace;
+
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
+    }
+
+    #[test]
+    fn test_expressions() {
+        init_logger();
+        let cases = [
+            ("-123 * (45.67)", "(* (- 123) (group 45.67))"),
+            ("1 + 2 * 3", "(+ 1 (* 2 3))"),
+            ("1 - 2 - 3", "(- (- 1 2) 3)"),
+            ("a = b = !c", "(= a (= b (! c)))"),
+            ("a or b and c", "(or a (and b c))"),
+            ("a ? b : c ? d : e", "(? a b (? c d e))"),
+            ("a, b", "(, a b)"),
+            ("f(1, 2).g = h()", "(= (. (call f 1 2) g) (call h))"),
+            ("\"a${x}b${y}\"", "(str \"a\" x \"b\" y)"),
+            ("\"say \\\"hi\\\"\"", "\"say \\\"hi\\\"\""),
+        ];
+        for (source, expected) in cases {
+            let mut interner = Interner::new();
+            let expression =
+                Parser::new(Scanner::new(source), &mut interner).parse().expect("Parsing failed.");
+            let printer = SexprPrinter::new(&interner);
+            assert_eq!(expression.accept(&printer).unwrap(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_program() {
+        init_logger();
+        let source = "
+            class B < A { f(x, y) { return super.f(this); } }
+            var a;
+            if (a) print nil; else { a = 1; return; }
+            while (true) a;
+        ";
+        let mut interner = Interner::new();
+        let statements =
+            Parser::new(Scanner::new(source), &mut interner).parse_program().into_result().unwrap();
+        assert_eq!(
+            SexprPrinter::new(&interner).print_program(&statements),
+            "(class B (< A) (fun f (x y) (return (call (. super f) this))))
+(var a)
+(if a (print nil) (block (; (= a 1)) (return)))
+(while true (; a))"
+        );
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Display,
+};
+
+use crate::{
+    diagnostic::Diagnostic,
+    interner::Symbol,
+    parser::ast::{
+        AssignExpr, BinaryExpr, BlockStmt, CallExpr, ClassStmt, ConditionalExpr, Expression,
+        ExpressionStmt, ExpressionVisitor, FunctionStmt, GetExpr, GroupingExpr, IfStmt,
+        InterpolationExpr, LiteralExpr, LogicalExpr, NodeId, PrintStmt, ReturnStmt, SetExpr,
+        Statement, StatementVisitor, SuperExpr, ThisExpr, UnaryExpr, VarStmt, VariableExpr,
+        WhileStmt,
+    },
+    scanner::OwnedToken,
+};
+
+/// How many scopes to walk outwards from the innermost one to find the
+/// binding for each resolved variable reference. Globals are not recorded.
+pub type Locals = HashMap<NodeId, usize>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass that binds every local variable reference to the scope that
+/// declares it, and reports scope errors before anything runs.
+pub struct Resolver {
+    /// One map per local scope, from name to whether its initializer has
+    /// finished resolving.
+    scopes: RefCell<Vec<HashMap<Symbol, bool>>>,
+    locals: RefCell<Locals>,
+    errors: RefCell<Vec<ResolverError>>,
+    current_function: Cell<FunctionType>,
+    current_class: Cell<ClassType>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver {
+            scopes: RefCell::new(vec![]),
+            locals: RefCell::new(HashMap::new()),
+            errors: RefCell::new(vec![]),
+            current_function: Cell::new(FunctionType::None),
+            current_class: Cell::new(ClassType::None),
+        }
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver::default()
+    }
+
+    /// Resolves a whole program, returning either the scope distance of every
+    /// local reference or every error that was found.
+    pub fn resolve(self, statements: &[Statement]) -> Result<Locals, Vec<ResolverError>> {
+        self.resolve_statements(statements);
+        let errors = self.errors.into_inner();
+        if errors.is_empty() { Ok(self.locals.into_inner()) } else { Err(errors) }
+    }
+
+    fn resolve_statements(&self, statements: &[Statement]) {
+        for statement in statements {
+            let _ = statement.accept(self);
+        }
+    }
+
+    fn resolve_expression(&self, expression: &Expression) {
+        let _ = expression.accept(self);
+    }
+
+    fn resolve_function(&self, function: &FunctionStmt, function_type: FunctionType) {
+        let enclosing_function = self.current_function.replace(function_type);
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&function.body);
+        self.end_scope();
+        self.current_function.set(enclosing_function);
+    }
+
+    fn resolve_local(&self, id: NodeId, name: Symbol) {
+        let scopes = self.scopes.borrow();
+        if let Some(depth) = scopes.iter().rev().position(|scope| scope.contains_key(&name)) {
+            self.locals.borrow_mut().insert(id, depth);
+        }
+    }
+
+    fn begin_scope(&self) {
+        self.scopes.borrow_mut().push(HashMap::new());
+    }
+
+    fn end_scope(&self) {
+        self.scopes.borrow_mut().pop();
+    }
+
+    fn declare(&self, name: &OwnedToken) {
+        let mut scopes = self.scopes.borrow_mut();
+        let Some(scope) = scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.symbol()) {
+            self.error(ResolverError::AlreadyDeclared(name.clone()));
+        }
+        scope.insert(name.symbol(), false);
+    }
+
+    fn define(&self, name: &OwnedToken) {
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert(name.symbol(), true);
+        }
+    }
+
+    fn error(&self, error: ResolverError) {
+        self.errors.borrow_mut().push(error);
+    }
+}
+
+impl StatementVisitor<(), Infallible> for Resolver {
+    fn visit_block(&self, stmt: &BlockStmt) -> Result<(), Infallible> {
+        self.begin_scope();
+        self.resolve_statements(&stmt.statements);
+        self.end_scope();
+        Ok(())
+    }
+
+    fn visit_class(&self, stmt: &ClassStmt) -> Result<(), Infallible> {
+        let enclosing_class = self.current_class.replace(ClassType::Class);
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.symbol() == stmt.name.symbol() {
+                self.error(ResolverError::InheritsFromItself(superclass.name.clone()));
+            }
+            self.current_class.set(ClassType::Subclass);
+            self.visit_variable(superclass)?;
+            self.begin_scope();
+            self.scopes.borrow_mut().last_mut().unwrap().insert(Symbol::SUPER, true);
+        }
+
+        self.begin_scope();
+        self.scopes.borrow_mut().last_mut().unwrap().insert(Symbol::THIS, true);
+        for method in &stmt.methods {
+            let function_type = if method.name.symbol() == Symbol::INIT {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class.set(enclosing_class);
+        Ok(())
+    }
+
+    fn visit_expression(&self, stmt: &ExpressionStmt) -> Result<(), Infallible> {
+        self.resolve_expression(&stmt.expression);
+        Ok(())
+    }
+
+    fn visit_function(&self, stmt: &FunctionStmt) -> Result<(), Infallible> {
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionType::Function);
+        Ok(())
+    }
+
+    fn visit_if(&self, stmt: &IfStmt) -> Result<(), Infallible> {
+        self.resolve_expression(&stmt.condition);
+        stmt.then_branch.accept(self)?;
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self)?;
+        }
+        Ok(())
+    }
+
+    fn visit_print(&self, stmt: &PrintStmt) -> Result<(), Infallible> {
+        self.resolve_expression(&stmt.expression);
+        Ok(())
+    }
+
+    fn visit_return(&self, stmt: &ReturnStmt) -> Result<(), Infallible> {
+        if self.current_function.get() == FunctionType::None {
+            self.error(ResolverError::TopLevelReturn(stmt.keyword.clone()));
+        }
+        if let Some(value) = &stmt.value {
+            if self.current_function.get() == FunctionType::Initializer {
+                self.error(ResolverError::ReturnValueFromInitializer(stmt.keyword.clone()));
+            }
+            self.resolve_expression(value);
+        }
+        Ok(())
+    }
+
+    fn visit_var(&self, stmt: &VarStmt) -> Result<(), Infallible> {
+        self.declare(&stmt.name);
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_expression(initializer);
+        }
+        self.define(&stmt.name);
+        Ok(())
+    }
+
+    fn visit_while(&self, stmt: &WhileStmt) -> Result<(), Infallible> {
+        self.resolve_expression(&stmt.condition);
+        stmt.body.accept(self)
+    }
+}
+
+impl ExpressionVisitor<(), Infallible> for Resolver {
+    fn visit_assign(&self, expr: &AssignExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.value);
+        self.resolve_local(expr.id, expr.name.symbol());
+        Ok(())
+    }
+
+    fn visit_binary(&self, expr: &BinaryExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.left);
+        self.resolve_expression(&expr.right);
+        Ok(())
+    }
+
+    fn visit_call(&self, expr: &CallExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.callee);
+        for argument in &expr.arguments {
+            self.resolve_expression(argument);
+        }
+        Ok(())
+    }
+
+    fn visit_conditional(&self, expr: &ConditionalExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.condition);
+        self.resolve_expression(&expr.then_branch);
+        self.resolve_expression(&expr.else_branch);
+        Ok(())
+    }
+
+    fn visit_get(&self, expr: &GetExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.object);
+        Ok(())
+    }
+
+    fn visit_grouping(&self, expr: &GroupingExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.expression);
+        Ok(())
+    }
+
+    fn visit_interpolation(&self, expr: &InterpolationExpr) -> Result<(), Infallible> {
+        for expression in &expr.expressions {
+            self.resolve_expression(expression);
+        }
+        Ok(())
+    }
+
+    fn visit_literal(&self, _expr: &LiteralExpr) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn visit_logical(&self, expr: &LogicalExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.left);
+        self.resolve_expression(&expr.right);
+        Ok(())
+    }
+
+    fn visit_set(&self, expr: &SetExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.value);
+        self.resolve_expression(&expr.object);
+        Ok(())
+    }
+
+    fn visit_super(&self, expr: &SuperExpr) -> Result<(), Infallible> {
+        match self.current_class.get() {
+            ClassType::None => self.error(ResolverError::SuperOutsideClass(expr.keyword.clone())),
+            ClassType::Class => {
+                self.error(ResolverError::SuperWithoutSuperclass(expr.keyword.clone()))
+            },
+            ClassType::Subclass => {},
+        }
+        self.resolve_local(expr.id, expr.keyword.symbol());
+        Ok(())
+    }
+
+    fn visit_this(&self, expr: &ThisExpr) -> Result<(), Infallible> {
+        if self.current_class.get() == ClassType::None {
+            self.error(ResolverError::ThisOutsideClass(expr.keyword.clone()));
+            return Ok(());
+        }
+        self.resolve_local(expr.id, expr.keyword.symbol());
+        Ok(())
+    }
+
+    fn visit_unary(&self, expr: &UnaryExpr) -> Result<(), Infallible> {
+        self.resolve_expression(&expr.right);
+        Ok(())
+    }
+
+    fn visit_variable(&self, expr: &VariableExpr) -> Result<(), Infallible> {
+        let in_own_initializer = self
+            .scopes
+            .borrow()
+            .last()
+            .is_some_and(|scope| scope.get(&expr.name.symbol()) == Some(&false));
+        if in_own_initializer {
+            self.error(ResolverError::ReadInOwnInitializer(expr.name.clone()));
+        }
+        self.resolve_local(expr.id, expr.name.symbol());
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ResolverError {
+    AlreadyDeclared(OwnedToken),
+    ReadInOwnInitializer(OwnedToken),
+    TopLevelReturn(OwnedToken),
+    ReturnValueFromInitializer(OwnedToken),
+    ThisOutsideClass(OwnedToken),
+    SuperOutsideClass(OwnedToken),
+    SuperWithoutSuperclass(OwnedToken),
+    InheritsFromItself(OwnedToken),
+}
+
+impl ResolverError {
+    pub fn token(&self) -> &OwnedToken {
+        match self {
+            ResolverError::AlreadyDeclared(token)
+            | ResolverError::ReadInOwnInitializer(token)
+            | ResolverError::TopLevelReturn(token)
+            | ResolverError::ReturnValueFromInitializer(token)
+            | ResolverError::ThisOutsideClass(token)
+            | ResolverError::SuperOutsideClass(token)
+            | ResolverError::SuperWithoutSuperclass(token)
+            | ResolverError::InheritsFromItself(token) => token,
+        }
+    }
+}
+
+impl ResolverError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ResolverError::AlreadyDeclared(_) => "Already a variable with this name in this scope.",
+            ResolverError::ReadInOwnInitializer(_) => {
+                "Can't read local variable in its own initializer."
+            },
+            ResolverError::TopLevelReturn(_) => "Can't return from top-level code.",
+            ResolverError::ReturnValueFromInitializer(_) => {
+                "Can't return a value from an initializer."
+            },
+            ResolverError::ThisOutsideClass(_) => "Can't use 'this' outside of a class.",
+            ResolverError::SuperOutsideClass(_) => "Can't use 'super' outside of a class.",
+            ResolverError::SuperWithoutSuperclass(_) => {
+                "Can't use 'super' in a class with no superclass."
+            },
+            ResolverError::InheritsFromItself(_) => "A class can't inherit from itself.",
+        }
+    }
+}
+
+impl Display for ResolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let token = self.token();
+        write!(f, "[line {}] Error at '{}': {}", token.line, token.lexeme, self.message())
+    }
+}
+
+impl From<&ResolverError> for Diagnostic {
+    fn from(error: &ResolverError) -> Self {
+        let (code, label) = match error {
+            ResolverError::AlreadyDeclared(_) => ("E0201", "declared again here"),
+            ResolverError::ReadInOwnInitializer(_) => ("E0202", "read before it is initialized"),
+            ResolverError::TopLevelReturn(_) => ("E0203", "not inside a function"),
+            ResolverError::ReturnValueFromInitializer(_) => {
+                ("E0204", "initializers always return 'this'")
+            },
+            ResolverError::ThisOutsideClass(_) => ("E0205", "not inside a method"),
+            ResolverError::SuperOutsideClass(_) => ("E0206", "not inside a method"),
+            ResolverError::SuperWithoutSuperclass(_) => ("E0207", "this class has no superclass"),
+            ResolverError::InheritsFromItself(_) => ("E0208", "refers to the class being declared"),
+        };
+        Diagnostic::error(error.message()).with_code(code).with_primary(error.token().span, label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter::Trace;
+
+    use super::*;
+    use crate::{interner::Interner, parser::Parser};
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(false).filter_level(Trace).try_init();
+    }
+
+    fn resolve(source: &str) -> Result<Locals, Vec<ResolverError>> {
+        let tokens = crate::scanner::Scanner::new(source);
+        let statements = Parser::new(tokens, &mut Interner::new())
+            .parse_program()
+            .into_result()
+            .expect("Parsing failed.");
+        Resolver::new().resolve(&statements)
+    }
+
+    #[test]
+    fn test_records_scope_distances() {
+        init_logger();
+        let locals = resolve("var a; { var b; { print a; print b; b = 1; } }").unwrap();
+        let mut depths = locals.values().copied().collect::<Vec<_>>();
+        depths.sort();
+        // `a` is global and therefore not recorded; `b` is read and assigned
+        // one scope out.
+        assert_eq!(depths, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_read_in_own_initializer() {
+        init_logger();
+        let errors = resolve("var a = 1; { var a = a; }").unwrap_err();
+        assert!(matches!(errors[..], [ResolverError::ReadInOwnInitializer(_)]));
+        // Globals may refer to themselves; the check only applies to locals.
+        assert!(resolve("var a = 1; var a = a;").is_ok());
+    }
+
+    #[test]
+    fn test_scope_errors() {
+        init_logger();
+        let errors = resolve(
+            "
+            return 1;
+            print this;
+            print super.x;
+            class A { m() { return super.m(); } init() { return 1; } }
+            class B < B {}
+            fun f() { var x; var x; }
+            ",
+        )
+        .unwrap_err();
+        assert!(matches!(errors[..], [
+            ResolverError::TopLevelReturn(_),
+            ResolverError::ThisOutsideClass(_),
+            ResolverError::SuperOutsideClass(_),
+            ResolverError::SuperWithoutSuperclass(_),
+            ResolverError::ReturnValueFromInitializer(_),
+            ResolverError::InheritsFromItself(_),
+            ResolverError::AlreadyDeclared(_),
+        ]));
+    }
+
+    #[test]
+    fn test_valid_program_resolves() {
+        init_logger();
+        let result = resolve(
+            "
+            class A { init() { this.x = 1; return; } get() { return this.x; } }
+            class B < A { get() { return super.get() + 1; } }
+            fun counter() { var i = 0; fun inc() { i = i + 1; return i; } return inc; }
+            ",
+        );
+        assert!(result.is_ok());
+    }
+}
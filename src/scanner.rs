@@ -1,16 +1,47 @@
-use std::fmt::{Debug, Display, Formatter};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fmt::{Debug, Display, Formatter},
+    iter::FusedIterator,
+    ops::Range,
+};
 
 use log::debug;
 use serde::{Deserialize, Serialize};
 
-pub fn scan(source: &str) -> impl Iterator<Item = Token> {
-    Scanner::scan_string(source.to_string()).tokens.into_iter()
+use crate::{
+    diagnostic::Diagnostic,
+    interner::{Interner, Symbol},
+    span::{Span, TextEdit},
+};
+
+/// The tokens of `source`, skipping any that failed to scan.
+pub fn scan(source: &str) -> impl Iterator<Item = Token<'_>> {
+    Scanner::new(source).filter_map(Result::ok)
 }
 
-pub struct Scanner {
-    errors: Vec<ScannerError>,
-    source: Vec<char>,
-    tokens: Vec<Token>,
+/// Walks the source by byte offset, producing tokens on demand. Tokens borrow
+/// their lexemes from the source, so scanning allocates only for string
+/// literals. Errors come out of the iterator where they occur, and scanning
+/// carries on after them up to the final `Eof` token.
+///
+/// A lossless scanner also attaches the whitespace, comments and unscannable
+/// text around each token to it as trivia, so that the tokens with their
+/// trivia add up to the source byte for byte.
+pub struct Scanner<'src> {
+    source: &'src str,
+    /// Results already scanned but not handed out yet.
+    pending: VecDeque<Result<Token<'src>, ScannerError>>,
+    /// For each interpolation in a string that the scanner is inside of,
+    /// innermost last, how many braces are open in it. The `}` that closes
+    /// an interpolation resumes its string.
+    interpolations: Vec<usize>,
+    /// Set once the `Eof` token has been produced.
+    done: bool,
+    /// Whether to keep trivia, and the trivia leading up to the next token.
+    lossless: bool,
+    trivia: Vec<Trivia<'src>>,
+    /// Byte offsets of the start of the current lexeme and of the next char.
     start: usize,
     offset: usize,
     line: usize,
@@ -18,20 +49,15 @@ pub struct Scanner {
 }
 
 /// Token scanning
-impl Scanner {
-    pub fn scan_string(source: String) -> Scanner {
-        // hack until I decide what to do with scan(&mut self)
-        let mut scanner = Scanner::new(source);
-        let tokens = scanner.scan().collect();
-        scanner.tokens = tokens;
-        scanner
-    }
-
-    fn new(source: String) -> Scanner {
+impl<'src> Scanner<'src> {
+    pub fn new(source: &'src str) -> Scanner<'src> {
         Scanner {
-            errors: vec![],
-            source: source.chars().collect(),
-            tokens: vec![],
+            source,
+            pending: VecDeque::new(),
+            interpolations: vec![],
+            done: false,
+            lossless: false,
+            trivia: vec![],
             start: 0,
             offset: 0,
             line: 1,
@@ -39,31 +65,39 @@ impl Scanner {
         }
     }
 
-    // todo should fill inner state?
-    fn scan(&mut self) -> impl Iterator<Item = Token> {
-        let mut result = vec![];
-        while !self.is_at_end() {
-            self.start = self.offset;
-            if let Some(token) = self.scan_token() {
-                result.push(token);
-            }
+    /// A scanner whose tokens carry their trivia.
+    pub fn lossless(source: &'src str) -> Scanner<'src> {
+        Scanner {
+            lossless: true,
+            ..Scanner::new(source)
         }
-        result.push(Token::eof(self.line, self.character + 1));
-        result.into_iter()
-    }
-
-    pub fn get_tokens(&self) -> Vec<Token> {
-        self.tokens.clone()
     }
 
-    fn scan_token(&mut self) -> Option<Token> {
+    fn scan_token(&mut self) -> Scanned<'src> {
         let c = self.advance();
         match c {
             '(' => self.token(TokenType::LeftParen),
             ')' => self.token(TokenType::RightParen),
-            '{' => self.token(TokenType::LeftBrace),
-            '}' => self.token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.token(TokenType::LeftBrace)
+            },
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string(true)
+                },
+                Some(depth) => {
+                    *depth -= 1;
+                    self.token(TokenType::RightBrace)
+                },
+                None => self.token(TokenType::RightBrace),
+            },
             ',' => self.token(TokenType::Comma),
+            '?' => self.token(TokenType::Question),
+            ':' => self.token(TokenType::Colon),
             '.' => self.token(TokenType::Dot),
             '-' => self.token(TokenType::Minus),
             '+' => self.token(TokenType::Plus),
@@ -75,115 +109,664 @@ impl Scanner {
             '>' => self.match_token_or('=', TokenType::GreaterEqual, TokenType::Greater),
             '/' => {
                 if self.check('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
-                    None
+                    self.line_comment();
+                    self.skip(comment_kind(self.lexeme()))
                 } else if self.check('*') {
-                    loop {
-                        let peek = self.peek();
-                        if peek == '\n' {
-                            self.next_line();
-                        } else if peek == '*' && self.peek_next() == '/' {
-                            self.advance();
-                            self.advance();
-                            break;
-                        }
-                        self.advance();
+                    let line = self.line;
+                    if self.block_comment() {
+                        self.skip(comment_kind(self.lexeme()))
+                    } else {
+                        self.skip_error(
+                            comment_kind(self.lexeme()),
+                            ScannerError::UnterminatedComment {
+                                line,
+                                span: self.span(),
+                            },
+                        )
                     }
-                    None
                 } else {
                     self.token(TokenType::Slash)
                 }
             },
-            ' ' | '\r' | '\t' => None,
+            ' ' | '\r' | '\t' => self.skip(TriviaKind::Whitespace),
             '\n' => {
                 self.next_line();
-                None
+                self.skip(TriviaKind::Newline)
             },
-            '"' => self.string(),
+            '"' => self.string(false),
             c if is_digit(c) => self.number(),
-            c if is_alpha(c) => self.identifier(),
-            _ => {
-                self.error("", format!("Unknown character '{}'", c).as_str());
-                None
-            },
+            c if is_identifier_start(c) => self.identifier(),
+            _ => self.skip_error(TriviaKind::Skipped, ScannerError::UnexpectedCharacter {
+                character: c,
+                line: self.line,
+                span: self.span(),
+            }),
         }
     }
 
-    fn string(&mut self) -> Option<Token> {
+    /// Skips the rest of a `//` comment, up to the end of the line.
+    fn line_comment(&mut self) {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+    }
+
+    /// Skips a `/* ... */` comment, whose opening `/*` has been consumed.
+    /// Comments nest, so every `/*` inside needs its own `*/`. Returns
+    /// whether the comment was closed before the end of the source.
+    fn block_comment(&mut self) -> bool {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                '\0' if self.is_at_end() => return false,
+                '\n' => self.next_line(),
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                },
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                },
+                _ => {},
+            }
+        }
+        true
+    }
+
+    /// Scans the trivia following a token up to the end of its line, which
+    /// the token keeps as its trailing trivia. A comment left unclosed is not
+    /// taken, so that it is reported where it starts as the next lexeme.
+    fn trailing_trivia(&mut self) -> Vec<Trivia<'src>> {
+        let leading = std::mem::take(&mut self.trivia);
+        loop {
+            self.start = self.offset;
+            match (self.peek(), self.peek_next()) {
+                (' ' | '\r' | '\t', _) => {
+                    self.advance();
+                    self.skip(TriviaKind::Whitespace);
+                },
+                ('/', '/') => {
+                    self.line_comment();
+                    self.skip(comment_kind(self.lexeme()));
+                },
+                ('/', '*') => {
+                    let (offset, line, character) = (self.offset, self.line, self.character);
+                    self.advance();
+                    self.advance();
+                    if !self.block_comment() {
+                        (self.offset, self.line, self.character) = (offset, line, character);
+                        break;
+                    }
+                    self.skip(comment_kind(self.lexeme()));
+                },
+                _ => break,
+            }
+        }
+        std::mem::replace(&mut self.trivia, leading)
+    }
+
+    /// Scans a string, or the part of one up to an interpolated `${` or
+    /// after the `}` ending one, if `resumed`. Decodes escapes as it goes. A
+    /// string with bad escapes still becomes a token, after an error for
+    /// each of them, so the parser is not thrown off.
+    fn string(&mut self, resumed: bool) -> Scanned<'src> {
+        let mut value = String::new();
+        let mut errors = vec![];
+        let mut interpolates = false;
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.next_line();
+            match self.peek() {
+                '$' if self.peek_next() == '{' => {
+                    self.advance();
+                    self.advance();
+                    self.interpolations.push(0);
+                    interpolates = true;
+                    break;
+                },
+                '\\' => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(error) => errors.push(error),
+                },
+                '\n' => {
+                    self.next_line();
+                    value.push(self.advance());
+                },
+                _ => value.push(self.advance()),
             }
+        }
+        if self.is_at_end() && !interpolates {
+            return self.skip_error(TriviaKind::Skipped, ScannerError::UnterminatedString {
+                line: self.line,
+                span: self.span(),
+            });
+        }
+        if !interpolates {
             self.advance();
         }
-        if self.is_at_end() {
-            self.error("", "Unterminated string.");
-            return None;
+        let token_type = match (resumed, interpolates) {
+            (false, false) => TokenType::String,
+            (false, true) => TokenType::StringStart,
+            (true, true) => TokenType::StringMiddle,
+            (true, false) => TokenType::StringEnd,
+        };
+        let token = self.token_with_literal(token_type, LiteralValue::String(value));
+        if errors.is_empty() {
+            return token;
         }
+        for error in errors {
+            self.pending.extend(self.error(error));
+        }
+        self.pending.extend(token);
+        self.pending.pop_front()
+    }
+
+    /// escape → "\\" ( [ntr0"\\$] | "u{" HEX_DIGIT{1,6} "}" ) ;
+    fn escape(&mut self) -> Result<char, ScannerError> {
+        let start = self.offset;
+        self.advance();
+        let escaped = match self.peek() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => {
+                self.advance();
+                return self.unicode_escape(start);
+            },
+            // A backslash at the end of a line or of the source escapes
+            // nothing; leave the line break or the missing quote to `string`.
+            '\n' => return Err(self.invalid_escape(start)),
+            _ if self.is_at_end() => return Err(self.invalid_escape(start)),
+            _ => {
+                self.advance();
+                return Err(self.invalid_escape(start));
+            },
+        };
         self.advance();
-        let value: String = self.source[(self.start + 1)..(self.offset - 1)].iter().collect();
-        self.token_with_literal(TokenType::String, LiteralValue::String(value))
+        Ok(escaped)
     }
 
-    fn number(&mut self) -> Option<Token> {
-        while is_digit(self.peek()) {
-            self.advance();
+    /// The rest of a `\u{...}` escape starting at byte `start`, after the
+    /// `u`.
+    fn unicode_escape(&mut self, start: usize) -> Result<char, ScannerError> {
+        if !self.check('{') {
+            return Err(self.invalid_escape(start));
         }
-        if self.peek() == '.' && is_digit(self.peek_next()) {
+        let digits_start = self.offset;
+        while self.peek().is_ascii_hexdigit() {
             self.advance();
-            while is_digit(self.peek()) {
+        }
+        let digits = &self.source[digits_start..self.offset];
+        if !self.check('}') || digits.is_empty() || digits.len() > 6 {
+            return Err(self.invalid_escape(start));
+        }
+        let value = u32::from_str_radix(digits, 16).expect("at most six hex digits");
+        char::from_u32(value).ok_or_else(|| ScannerError::InvalidCodePoint {
+            value,
+            line: self.line,
+            span: Span::new(start, self.offset),
+        })
+    }
+
+    /// An error for the escape from byte `start` up to where scanning it
+    /// stopped.
+    fn invalid_escape(&self, start: usize) -> ScannerError {
+        ScannerError::InvalidEscape {
+            escape: self.source[start..self.offset].to_string(),
+            line: self.line,
+            span: Span::new(start, self.offset),
+        }
+    }
+
+    /// Takes in everything that could belong to the number, including
+    /// letters, so that a malformed literal is reported once as a whole
+    /// rather than split into several tokens.
+    fn number(&mut self) -> Scanned<'src> {
+        let decimal =
+            !(self.lexeme() == "0" && matches!(self.peek(), 'x' | 'X' | 'b' | 'B' | 'o' | 'O'));
+        // A `.` can only start the fraction, which comes before any exponent.
+        let mut dot_allowed = decimal;
+        loop {
+            let c = self.peek();
+            if c.is_ascii_alphanumeric() || c == '_' {
+                self.advance();
+                if decimal && matches!(c, 'e' | 'E') {
+                    dot_allowed = false;
+                    if matches!(self.peek(), '+' | '-') && is_digit(self.peek_next()) {
+                        self.advance();
+                    }
+                }
+            } else if dot_allowed && c == '.' && is_digit(self.peek_next()) {
+                dot_allowed = false;
                 self.advance();
+            } else {
+                break;
             }
         }
-        let lexeme = self.lexeme();
-        let Ok(number) = lexeme.parse::<f64>() else {
-            self.error("", format!("Failed to parse number {}.", lexeme).as_str());
-            return None;
-        };
-
-        self.token_with_literal(TokenType::Number, LiteralValue::Number(number))
+        match parse_number(self.lexeme()) {
+            Ok(number) => self.token_with_literal(TokenType::Number, LiteralValue::Number(number)),
+            Err((error, range)) => {
+                self.skip_error(TriviaKind::Skipped, ScannerError::InvalidNumber {
+                    error,
+                    line: self.line,
+                    span: Span::new(self.start + range.start, self.start + range.end),
+                })
+            },
+        }
     }
 
-    fn identifier(&mut self) -> Option<Token> {
-        while is_alpha(self.peek()) || is_digit(self.peek()) || self.peek() == '_' {
+    fn identifier(&mut self) -> Scanned<'src> {
+        while is_identifier_continue(self.peek()) {
             self.advance();
         }
-        let identifier_string = self.lexeme();
-        let token_type = TokenType::keyword(&identifier_string).unwrap_or(TokenType::Identifier);
+        let token_type = TokenType::keyword(self.lexeme()).unwrap_or(TokenType::Identifier);
         self.token(token_type)
     }
 }
 
+/// Incremental scanning
+impl<'src> Scanner<'src> {
+    /// Brings `tokens`, the tokens of the source before `edit`, up to date
+    /// with `source`, the source after it, so that they are what
+    /// [`TokenStore::new`] would make of it. Only the text around the edit is
+    /// scanned again: from a token boundary before it that is outside any
+    /// interpolation, up to the first token after it that is also among the
+    /// old tokens. The old tokens from there on are kept where they are, and
+    /// only moved once they are needed, so an edit costs about as much as the
+    /// tokens it touches.
+    pub fn relex(source: &'src str, tokens: &mut TokenStore, edit: &TextEdit) -> Relexed {
+        // Scanning a token looks at most a char or two past its end, so the
+        // token before the first one reaching the edit is scanned again too.
+        let touched = tokens.partition_point(edit.span.start);
+        let mut kept = touched.saturating_sub(1);
+        while kept > 0 && tokens.tokens[kept - 1].open != 0 {
+            kept -= 1;
+        }
+        tokens.settle(kept);
+        let mut scanner = Scanner::new(source);
+        if let Some(last) = kept.checked_sub(1).map(|index| &tokens.tokens[index]) {
+            scanner.offset = last.span.end;
+            scanner.line = last.line;
+            scanner.character = last.character;
+        }
+
+        // The old tokens from `kept` on are still to be moved by `pending`.
+        let (pending_bytes, pending_lines) = tokens.pending;
+        let shift = edit.text.len() as isize - edit.span.len() as isize;
+        let edit_end = edit.span.start + edit.text.len();
+        let mut rescanned = vec![];
+        let mut old = touched;
+        while let Some(result) = scanner.next() {
+            let Ok(token) = result else {
+                continue;
+            };
+            let token = KeptToken::new(&token, source, scanner.interpolations.len());
+            if token.span.start >= edit_end && token.open == 0 {
+                let old_start = token.span.start.wrapping_sub_signed(shift);
+                let start = |index: usize| {
+                    tokens.tokens[index].span.start.wrapping_add_signed(pending_bytes)
+                };
+                while old < tokens.tokens.len() && start(old) < old_start {
+                    old += 1;
+                }
+                let same = tokens.tokens.get(old).filter(|other| {
+                    start(old) == old_start
+                        && other.token_type == token.token_type
+                        && other.span.len() == token.span.len()
+                        && other.open == 0
+                });
+                if let Some(same) = same {
+                    let moved = Shift {
+                        bytes: shift,
+                        lines: token.line as isize - same.line as isize - pending_lines,
+                        columns: token.start_column as isize - same.start_column as isize,
+                    };
+                    let characters = token.character as isize - same.character as isize;
+                    debug!("Rescanned {} tokens, keeping {}", rescanned.len(), tokens.len() - old);
+                    tokens.realign(old, moved.columns, characters);
+                    return tokens.replace(kept..old, rescanned, moved);
+                }
+            }
+            rescanned.push(token);
+        }
+        let end = tokens.len();
+        tokens.replace(kept..end, rescanned, Shift::default())
+    }
+}
+
+/// The tokens of a source being edited, kept between edits for
+/// [`Scanner::relex`]. Tokens after the latest edits may not have been moved
+/// to where the edits put them yet; [`TokenStore::get`] gives them where
+/// they are now.
+#[derive(Debug, Default)]
+pub struct TokenStore {
+    tokens: Vec<KeptToken>,
+    /// The tokens from this index on are still to be moved by `pending`
+    /// bytes and lines.
+    moved_from: usize,
+    pending: (isize, isize),
+}
+
+impl TokenStore {
+    /// The tokens of `source`, skipping any that failed to scan.
+    pub fn new(source: &str) -> TokenStore {
+        let mut scanner = Scanner::new(source);
+        let mut tokens = vec![];
+        while let Some(result) = scanner.next() {
+            if let Ok(token) = result {
+                tokens.push(KeptToken::new(&token, source, scanner.interpolations.len()));
+            }
+        }
+        TokenStore {
+            moved_from: tokens.len(),
+            tokens,
+            pending: (0, 0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<KeptToken> {
+        let mut token = self.tokens.get(index)?.clone();
+        if index >= self.moved_from {
+            token.shift(self.pending);
+        }
+        Some(token)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = KeptToken> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+
+    /// The index of the first token ending at or after `offset`.
+    fn partition_point(&self, offset: usize) -> usize {
+        let end = |index: usize| {
+            let end = self.tokens[index].span.end;
+            if index >= self.moved_from { end.wrapping_add_signed(self.pending.0) } else { end }
+        };
+        let (mut low, mut high) = (0, self.tokens.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if end(middle) < offset {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    /// Moves the tokens between `index` and where the pending move starts,
+    /// so that it starts at `index` instead. Edits close to each other only
+    /// move the few tokens between them.
+    fn settle(&mut self, index: usize) {
+        let (bytes, lines) = self.pending;
+        for token in &mut self.tokens[self.moved_from.min(index)..self.moved_from.max(index)] {
+            if index > self.moved_from {
+                token.shift((bytes, lines));
+            } else {
+                token.shift((-bytes, -lines));
+            }
+        }
+        self.moved_from = index;
+    }
+
+    /// Moves the tokens from `index` on that start or end on the line the
+    /// first of them does by `columns` and `characters`, which the edit
+    /// before them changed the length of. Only the one line is affected, so
+    /// this is done right away rather than left pending.
+    fn realign(&mut self, index: usize, columns: isize, characters: isize) {
+        let (start_line, line) = (self.tokens[index].start_line, self.tokens[index].line);
+        for token in &mut self.tokens[index..] {
+            if token.start_line != start_line && token.line != line {
+                break;
+            }
+            if token.start_line == start_line {
+                token.start_column = token.start_column.wrapping_add_signed(columns);
+            }
+            if token.line == line {
+                token.character = token.character.wrapping_add_signed(characters);
+            }
+        }
+    }
+
+    /// Puts `rescanned` in place of the tokens in `range`, which starts
+    /// where the pending move does, and adds `shift` to the move of the
+    /// tokens after it.
+    fn replace(&mut self, range: Range<usize>, rescanned: Vec<KeptToken>, shift: Shift) -> Relexed {
+        let inserted = range.start..range.start + rescanned.len();
+        self.tokens.splice(range.clone(), rescanned);
+        self.moved_from = inserted.end;
+        self.pending = (self.pending.0 + shift.bytes, self.pending.1 + shift.lines);
+        if self.moved_from == self.tokens.len() {
+            self.pending = (0, 0);
+        }
+        Relexed {
+            replaced: range,
+            inserted,
+            shift,
+        }
+    }
+}
+
+/// A token as a [`TokenStore`] keeps it: its lexeme is the text under its
+/// span, and it knows where it starts as well as where it ends.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeptToken {
+    pub token_type: TokenType,
+    pub literal: Option<LiteralValue<String>>,
+    pub span: Span,
+    /// The line the token starts on, and how many chars into it.
+    pub start_line: usize,
+    pub start_column: usize,
+    /// Where the token ends, as for a [`Token`]. The editor only needs
+    /// where tokens start, so these are left out when serializing.
+    #[serde(skip)]
+    pub line: usize,
+    #[serde(skip)]
+    pub character: usize,
+    /// How many interpolations are open after the token.
+    #[serde(skip)]
+    open: usize,
+}
+
+impl KeptToken {
+    fn new(token: &Token<'_>, source: &str, open: usize) -> KeptToken {
+        let newlines = token.lexeme.matches('\n').count();
+        let start_column = match token.token_type {
+            TokenType::Eof => token.character - 1,
+            _ if newlines == 0 => token.character - token.lexeme.chars().count(),
+            _ => source[..token.span.start].chars().rev().take_while(|&c| c != '\n').count(),
+        };
+        KeptToken {
+            token_type: token.token_type.clone(),
+            literal: token.literal.clone(),
+            span: token.span,
+            start_line: token.line - newlines,
+            start_column,
+            line: token.line,
+            character: token.character,
+            open,
+        }
+    }
+
+    fn shift(&mut self, (bytes, lines): (isize, isize)) {
+        self.span = Span::new(
+            self.span.start.wrapping_add_signed(bytes),
+            self.span.end.wrapping_add_signed(bytes),
+        );
+        self.start_line = self.start_line.wrapping_add_signed(lines);
+        self.line = self.line.wrapping_add_signed(lines);
+    }
+}
+
+/// What [`Scanner::relex`] changed: the tokens in `replaced`, counting
+/// from before the edit, gave way to those in `inserted`, and the tokens
+/// after them moved by `shift`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relexed {
+    pub replaced: Range<usize>,
+    pub inserted: Range<usize>,
+    pub shift: Shift,
+}
+
+/// How far an edit moved the tokens after it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Shift {
+    pub bytes: isize,
+    pub lines: isize,
+    /// How far the tokens starting on the line that the first of them does
+    /// moved along it. Tokens on the lines after stay in their columns.
+    pub columns: isize,
+}
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Result<Token<'src>, ScannerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(scanned) = self.pending.pop_front() {
+            return Some(scanned);
+        }
+        while !self.is_at_end() {
+            self.start = self.offset;
+            if let Some(scanned) = self.scan_token() {
+                return Some(scanned);
+            }
+        }
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        self.emit(Token::eof(self.line, self.character + 1, Span::empty(self.offset)))
+    }
+}
+
+impl FusedIterator for Scanner<'_> {}
+
+/// What scanning one lexeme produced: nothing for whitespace and comments,
+/// otherwise a token or an error.
+type Scanned<'src> = Option<Result<Token<'src>, ScannerError>>;
+
+/// number → DIGITS ( "." DIGITS )? ( [eE] [+-]? DIGITS )?
+///          | "0" [xX] HEX_DIGITS | "0" [bB] BIN_DIGITS | "0" [oO] OCT_DIGITS ;
+///
+/// where a single `_` may separate any two digits. Returns the exact value,
+/// rounded to the nearest `f64`, or the first problem with the byte range of
+/// `lexeme` it is in.
+fn parse_number(lexeme: &str) -> Result<f64, (NumberError, Range<usize>)> {
+    let (radix, prefix) = match lexeme.get(..2) {
+        Some("0x" | "0X") => (16, 2),
+        Some("0b" | "0B") => (2, 2),
+        Some("0o" | "0O") => (8, 2),
+        _ => (10, 0),
+    };
+    let bytes = lexeme.as_bytes();
+    let is_digit = |index: usize| bytes.get(index).is_some_and(|b| (*b as char).is_digit(radix));
+    let mut fraction = false;
+    let mut exponent = false;
+    let mut index = prefix;
+    while index < bytes.len() {
+        // The scanner only hands over ASCII.
+        let c = bytes[index] as char;
+        match c {
+            _ if c.is_digit(radix) => {},
+            '_' => {
+                let end =
+                    index + lexeme[index..].find(|c| c != '_').unwrap_or(lexeme.len() - index);
+                if !is_digit(index - 1) || !is_digit(end) || end > index + 1 {
+                    return Err((NumberError::MisplacedSeparator, index..end));
+                }
+            },
+            '.' if radix == 10 && !fraction && !exponent => fraction = true,
+            'e' | 'E' if radix == 10 && !exponent => {
+                exponent = true;
+                let sign = matches!(bytes.get(index + 1), Some(b'+' | b'-'));
+                if !is_digit(index + 1 + sign as usize) {
+                    return Err((NumberError::MissingExponent, index..index + 1 + sign as usize));
+                }
+                index += sign as usize;
+            },
+            _ => {
+                return Err((
+                    NumberError::InvalidDigit {
+                        digit: c,
+                        radix,
+                    },
+                    index..index + 1,
+                ));
+            },
+        }
+        index += 1;
+    }
+    if lexeme.len() == prefix {
+        return Err((NumberError::MissingDigits, 0..prefix));
+    }
+
+    let digits: String = lexeme[prefix..].chars().filter(|&c| c != '_').collect();
+    let value = if radix == 10 {
+        digits.parse::<f64>().ok().filter(|value| value.is_finite())
+    } else {
+        u128::from_str_radix(&digits, radix).ok().map(|value| value as f64)
+    };
+    value.ok_or((NumberError::TooLarge, 0..lexeme.len()))
+}
+
 fn is_digit(c: char) -> bool {
     c.is_ascii_digit()
 }
 
-fn is_alpha(c: char) -> bool {
-    c.is_ascii_alphabetic()
+/// Identifiers follow UAX #31: XID_Start or `_`, then any XID_Continue.
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+/// The name an identifier lexeme stands for: its NFC form with the `nfc`
+/// feature, so that names which look the same are the same.
+#[cfg(feature = "nfc")]
+fn identifier_name(lexeme: &str) -> Cow<'_, str> {
+    use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+    if is_nfc(lexeme) { Cow::Borrowed(lexeme) } else { Cow::Owned(lexeme.nfc().collect()) }
+}
+
+#[cfg(not(feature = "nfc"))]
+fn identifier_name(lexeme: &str) -> Cow<'_, str> {
+    Cow::Borrowed(lexeme)
 }
 
 /// Token manipulation and utility functions
-impl Scanner {
+impl<'src> Scanner<'src> {
     fn next_line(&mut self) {
         self.line += 1;
         self.character = 0;
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() { '\0' } else { self.source[self.offset] }
+        self.source[self.offset..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.offset + 1 >= self.source.len() { '\0' } else { self.source[self.offset + 1] }
+        self.source[self.offset..].chars().nth(1).unwrap_or('\0')
     }
 
     fn check(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        if self.source[self.offset] != expected {
+        if self.peek() != expected {
             return false;
         }
         self.advance();
@@ -195,24 +778,70 @@ impl Scanner {
         expected: char,
         token_type_on_match: TokenType,
         or: TokenType,
-    ) -> Option<Token> {
+    ) -> Scanned<'src> {
         if self.check(expected) { self.token(token_type_on_match) } else { self.token(or) }
     }
 
-    fn lexeme(&self) -> String {
-        self.source[self.start..self.offset].iter().collect()
+    fn lexeme(&self) -> &'src str {
+        &self.source[self.start..self.offset]
     }
 
-    fn token(&mut self, token_type: TokenType) -> Option<Token> {
-        Some(Token::new(token_type, self.lexeme(), None, self.line, self.character))
+    fn token(&mut self, token_type: TokenType) -> Scanned<'src> {
+        self.emit(Token::new(
+            token_type,
+            self.lexeme(),
+            None,
+            self.line,
+            self.character,
+            self.span(),
+        ))
     }
 
     fn token_with_literal(
         &mut self,
         token_type: TokenType,
-        literal: LiteralValue,
-    ) -> Option<Token> {
-        Some(Token::new(token_type, self.lexeme(), Some(literal), self.line, self.character))
+        literal: LiteralValue<String>,
+    ) -> Scanned<'src> {
+        self.emit(Token::new(
+            token_type,
+            self.lexeme(),
+            Some(literal),
+            self.line,
+            self.character,
+            self.span(),
+        ))
+    }
+
+    fn emit(&mut self, mut token: Token<'src>) -> Scanned<'src> {
+        if self.lossless {
+            token.leading_trivia = std::mem::take(&mut self.trivia);
+            token.trailing_trivia = self.trailing_trivia();
+        }
+        Some(Ok(token))
+    }
+
+    /// Skips the current lexeme, keeping it as trivia in lossless mode.
+    /// Whitespace next to whitespace joins it.
+    fn skip(&mut self, kind: TriviaKind) -> Scanned<'src> {
+        if !self.lossless {
+            return None;
+        }
+        match self.trivia.last_mut() {
+            Some(last) if kind == TriviaKind::Whitespace && last.kind == kind => {
+                last.span.end = self.offset;
+                last.text = &self.source[last.span.start..self.offset];
+            },
+            _ => self.trivia.push(Trivia {
+                kind,
+                text: self.lexeme(),
+                span: self.span(),
+            }),
+        }
+        None
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.start, self.offset)
     }
 
     fn is_at_end(&self) -> bool {
@@ -220,104 +849,418 @@ impl Scanner {
     }
 
     fn advance(&mut self) -> char {
-        if self.is_at_end() {
+        let Some(result) = self.source[self.offset..].chars().next() else {
             return '\0';
-        }
-        let result = self.source[self.offset];
-        self.offset += 1;
+        };
+        self.offset += result.len_utf8();
         self.character += 1;
         result
     }
 }
 
 /// Error handling
-/// TODO: delegate to error accumulator later
-impl Scanner {
-    fn error(&mut self, location: &str, message: &str) {
-        let error = ScannerError {
-            location: location.to_string(),
-            line: self.line,
-            character: self.character,
-            message: message.to_string(),
-        };
-        debug!("Encountered error: {}.", error);
-        self.errors.push(error);
+impl<'src> Scanner<'src> {
+    fn error(&self, error: ScannerError) -> Scanned<'src> {
+        debug!("Encountered error: {}", error);
+        Some(Err(error))
     }
 
-    pub fn get_errors(&self) -> Vec<ScannerError> {
-        self.errors.clone()
+    /// Reports an error for a lexeme that does not become a token.
+    fn skip_error(&mut self, kind: TriviaKind, error: ScannerError) -> Scanned<'src> {
+        self.skip(kind);
+        self.error(error)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ScannerError {
-    location: String,
-    line: usize,
-    character: usize,
-    message: String,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScannerError {
+    UnexpectedCharacter { character: char, line: usize, span: Span },
+    UnterminatedString { line: usize, span: Span },
+    UnterminatedComment { line: usize, span: Span },
+    InvalidNumber { error: NumberError, line: usize, span: Span },
+    InvalidEscape { escape: String, line: usize, span: Span },
+    InvalidCodePoint { value: u32, line: usize, span: Span },
+}
+
+impl ScannerError {
+    pub fn line(&self) -> usize {
+        match self {
+            ScannerError::UnexpectedCharacter {
+                line, ..
+            }
+            | ScannerError::UnterminatedString {
+                line, ..
+            }
+            | ScannerError::UnterminatedComment {
+                line, ..
+            }
+            | ScannerError::InvalidNumber {
+                line, ..
+            }
+            | ScannerError::InvalidEscape {
+                line, ..
+            }
+            | ScannerError::InvalidCodePoint {
+                line, ..
+            } => *line,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ScannerError::UnexpectedCharacter {
+                span, ..
+            }
+            | ScannerError::UnterminatedString {
+                span, ..
+            }
+            | ScannerError::UnterminatedComment {
+                span, ..
+            }
+            | ScannerError::InvalidNumber {
+                span, ..
+            }
+            | ScannerError::InvalidEscape {
+                span, ..
+            }
+            | ScannerError::InvalidCodePoint {
+                span, ..
+            } => *span,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ScannerError::UnexpectedCharacter {
+                character, ..
+            } => format!("Unexpected character '{}'.", character),
+            ScannerError::UnterminatedString {
+                ..
+            } => "Unterminated string.".to_string(),
+            ScannerError::UnterminatedComment {
+                ..
+            } => "Unterminated block comment.".to_string(),
+            ScannerError::InvalidNumber {
+                error, ..
+            } => error.to_string(),
+            ScannerError::InvalidEscape {
+                escape, ..
+            } => format!("Invalid escape sequence '{}'.", escape),
+            ScannerError::InvalidCodePoint {
+                value, ..
+            } => format!("'\\u{{{:X}}}' is not a Unicode scalar value.", value),
+        }
+    }
 }
 
 impl Display for ScannerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}:{}:{}] {}", self.location, self.line, self.character, self.message)
+        write!(f, "[line {}] Error: {}", self.line(), self.message())
     }
 }
 
+impl From<&ScannerError> for Diagnostic {
+    fn from(error: &ScannerError) -> Self {
+        let diagnostic = Diagnostic::error(error.message());
+        match error {
+            ScannerError::UnexpectedCharacter {
+                span, ..
+            } => diagnostic.with_code("E0001").with_primary(*span, "not valid in Lox source"),
+            ScannerError::UnterminatedString {
+                span, ..
+            } => diagnostic
+                .with_code("E0002")
+                .with_primary(Span::new(span.start, span.start + 1), "string starts here")
+                .with_secondary(Span::empty(span.end), "source ends before the closing '\"'")
+                .with_help("add a '\"' to close the string"),
+            ScannerError::UnterminatedComment {
+                span, ..
+            } => diagnostic
+                .with_code("E0006")
+                .with_primary(Span::new(span.start, span.start + 2), "comment starts here")
+                .with_secondary(Span::empty(span.end), "source ends before the closing '*/'")
+                .with_help("add a '*/' to close the comment"),
+            ScannerError::InvalidNumber {
+                error,
+                span,
+                ..
+            } => {
+                let diagnostic = diagnostic.with_code("E0003");
+                match error {
+                    NumberError::MissingDigits => {
+                        diagnostic.with_primary(*span, "expected digits after this prefix")
+                    },
+                    NumberError::MissingExponent => {
+                        diagnostic.with_primary(*span, "expected digits after this")
+                    },
+                    NumberError::MisplacedSeparator => diagnostic
+                        .with_primary(*span, "not between two digits")
+                        .with_help("use a single '_' between digits, like 1_000_000"),
+                    NumberError::InvalidDigit {
+                        radix, ..
+                    } => diagnostic
+                        .with_primary(*span, format!("not a {} digit", radix_name(*radix))),
+                    NumberError::TooLarge => {
+                        diagnostic.with_primary(*span, "too large to represent")
+                    },
+                }
+            },
+            ScannerError::InvalidEscape {
+                escape,
+                span,
+                ..
+            } if escape.starts_with("\\u") => diagnostic
+                .with_code("E0004")
+                .with_primary(*span, "malformed Unicode escape")
+                .with_help("write a code point as 1 to 6 hex digits in braces, like '\\u{1F600}'"),
+            ScannerError::InvalidEscape {
+                span, ..
+            } => diagnostic
+                .with_code("E0004")
+                .with_primary(*span, "unknown escape")
+                .with_note("valid escapes are \\n, \\t, \\r, \\0, \\\", \\\\, \\$ and \\u{...}")
+                .with_help("write '\\\\' for a literal backslash"),
+            ScannerError::InvalidCodePoint {
+                span, ..
+            } => diagnostic
+                .with_code("E0005")
+                .with_primary(*span, "not a character")
+                .with_note("surrogates and values above 10FFFF cannot appear in strings"),
+        }
+    }
+}
+
+/// What is wrong with a malformed number literal.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum LiteralValue {
-    String(String),
+pub enum NumberError {
+    MissingDigits,
+    MissingExponent,
+    MisplacedSeparator,
+    InvalidDigit { digit: char, radix: u32 },
+    TooLarge,
+}
+
+impl Display for NumberError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberError::MissingDigits => write!(f, "Number literal has no digits."),
+            NumberError::MissingExponent => write!(f, "Exponent has no digits."),
+            NumberError::MisplacedSeparator => {
+                write!(f, "Digit separator '_' must be between two digits.")
+            },
+            NumberError::InvalidDigit {
+                digit,
+                radix,
+            } => write!(f, "Invalid digit '{}' in {} literal.", digit, radix_name(*radix)),
+            NumberError::TooLarge => write!(f, "Number literal is too large."),
+        }
+    }
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+/// The value of a literal. Strings come out of the scanner as they are, and
+/// are interned once parsed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LiteralValue<S = Symbol> {
+    String(S),
     Number(f64),
     Boolean(bool),
     Nil,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Token {
+impl LiteralValue<String> {
+    pub fn intern(&self, interner: &mut Interner) -> LiteralValue {
+        match self {
+            LiteralValue::String(s) => LiteralValue::String(interner.intern(s)),
+            LiteralValue::Number(n) => LiteralValue::Number(*n),
+            LiteralValue::Boolean(b) => LiteralValue::Boolean(*b),
+            LiteralValue::Nil => LiteralValue::Nil,
+        }
+    }
+}
+
+/// A token borrowing its lexeme from the source it was scanned from.
+#[derive(Clone, Serialize)]
+pub struct Token<'src> {
     pub token_type: TokenType,
-    pub lexeme: String,
-    pub literal: Option<LiteralValue>,
+    pub lexeme: &'src str,
+    pub literal: Option<LiteralValue<String>>,
     pub line: usize,
     pub character: usize,
+    /// Byte range of the lexeme in the source it was scanned from.
+    pub span: Span,
+    /// What comes before the lexeme since the previous token's trailing
+    /// trivia, and after it up to the end of the line. Only kept by a
+    /// lossless scanner.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub leading_trivia: Vec<Trivia<'src>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trailing_trivia: Vec<Trivia<'src>>,
 }
 
-impl Token {
+impl<'src> Token<'src> {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
-        literal: Option<LiteralValue>,
+        lexeme: &'src str,
+        literal: Option<LiteralValue<String>>,
         line: usize,
         character: usize,
-    ) -> Token {
+        span: Span,
+    ) -> Token<'src> {
         Token {
             token_type,
             lexeme,
             literal,
             line,
             character,
+            span,
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
         }
     }
 
-    pub fn eof(line: usize, character: usize) -> Token {
-        Token::new(TokenType::Eof, "".to_string(), None, line, character)
+    pub fn eof(line: usize, character: usize, span: Span) -> Token<'src> {
+        Token::new(TokenType::Eof, "", None, line, character, span)
+    }
+
+    /// This token, no longer borrowing from the source, with its name if it
+    /// is an identifier and its value if it is a string interned in
+    /// `interner`.
+    pub fn to_owned_token(&self, interner: &mut Interner) -> OwnedToken {
+        let (lexeme, symbol) = match self.token_type {
+            TokenType::Identifier => {
+                let name = identifier_name(self.lexeme);
+                let symbol = interner.intern(&name);
+                (name.into_owned(), Some(symbol))
+            },
+            _ => (self.lexeme.to_string(), None),
+        };
+        OwnedToken {
+            token_type: self.token_type.clone(),
+            lexeme,
+            symbol,
+            literal: self.literal.as_ref().map(|literal| literal.intern(interner)),
+            line: self.line,
+            character: self.character,
+            span: self.span,
+        }
     }
 }
 
-impl Debug for Token {
+/// Source text between tokens, kept by a lossless scanner.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub text: &'src str,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriviaKind {
+    /// Spaces, tabs and carriage returns.
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+    /// A `///` line comment or a `/** ... */` block comment.
+    DocComment,
+    /// Text that failed to scan, such as an unexpected character.
+    Skipped,
+}
+
+fn comment_kind(text: &str) -> TriviaKind {
+    let line_doc = text.starts_with("///") && !text.starts_with("////");
+    let block_doc = text.starts_with("/**") && !text.starts_with("/***") && text != "/**/";
+    if line_doc || block_doc {
+        TriviaKind::DocComment
+    } else if text.starts_with("//") {
+        TriviaKind::LineComment
+    } else {
+        TriviaKind::BlockComment
+    }
+}
+
+impl Debug for Token<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if let Some(literal) = &self.literal {
-            write!(
-                f,
-                "{:?}({:?},{:?})'{}'[{:?}]",
-                self.token_type, self.line, self.character, self.lexeme, literal
-            )
-        } else {
-            write!(
-                f,
-                "{:?}({:?},{:?})'{}'",
-                self.token_type, self.line, self.character, self.lexeme
-            )
+        debug_token(f, &self.token_type, self.line, self.character, self.lexeme, &self.literal)
+    }
+}
+
+/// A token for the AST and anything else that outlives the source. The
+/// names of identifiers are interned for the resolver and interpreter to
+/// look up, and so are the values of strings; the lexeme is kept as text for
+/// messages. Identifier lexemes hold the name the identifier stands for,
+/// which may differ from the source text.
+#[derive(Clone)]
+pub struct OwnedToken {
+    pub token_type: TokenType,
+    pub lexeme: String,
+    symbol: Option<Symbol>,
+    pub literal: Option<LiteralValue>,
+    pub line: usize,
+    pub character: usize,
+    pub span: Span,
+}
+
+impl OwnedToken {
+    /// A token that is not an identifier, which has no name to intern.
+    pub fn new(
+        token_type: TokenType,
+        lexeme: impl Into<String>,
+        literal: Option<LiteralValue>,
+        line: usize,
+        character: usize,
+        span: Span,
+    ) -> OwnedToken {
+        OwnedToken {
+            token_type,
+            lexeme: lexeme.into(),
+            symbol: None,
+            literal,
+            line,
+            character,
+            span,
         }
     }
+
+    /// The name the token binds or looks up: the interned name of an
+    /// identifier, or the predefined one of `this` or `super`.
+    pub fn symbol(&self) -> Symbol {
+        match self.token_type {
+            TokenType::This => Symbol::THIS,
+            TokenType::Super => Symbol::SUPER,
+            _ => self.symbol.expect("only identifiers have a name"),
+        }
+    }
+}
+
+impl Debug for OwnedToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        debug_token(f, &self.token_type, self.line, self.character, &self.lexeme, &self.literal)
+    }
+}
+
+fn debug_token(
+    f: &mut Formatter<'_>,
+    token_type: &TokenType,
+    line: usize,
+    character: usize,
+    lexeme: &str,
+    literal: &Option<LiteralValue<impl Debug>>,
+) -> std::fmt::Result {
+    if let Some(literal) = literal {
+        write!(f, "{:?}({:?},{:?})'{}'[{:?}]", token_type, line, character, lexeme, literal)
+    } else {
+        write!(f, "{:?}({:?},{:?})'{}'", token_type, line, character, lexeme)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -334,6 +1277,8 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Question,
+    Colon,
 
     // One or two character tokens.
     Bang,
@@ -349,6 +1294,11 @@ pub enum TokenType {
     Identifier,
     String,
     Number,
+    /// The parts of a string with interpolations: up to the first `${`,
+    /// between a `}` and the next `${`, and from the last `}`.
+    StringStart,
+    StringMiddle,
+    StringEnd,
 
     // Keywords.
     And,
@@ -395,7 +1345,59 @@ impl TokenType {
     }
 }
 
-pub fn pretty(tokens: &Vec<Token>) -> String {
+/// How a token type is referred to in error messages.
+impl Display for TokenType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            TokenType::LeftParen => "'('",
+            TokenType::RightParen => "')'",
+            TokenType::LeftBrace => "'{'",
+            TokenType::RightBrace => "'}'",
+            TokenType::Comma => "','",
+            TokenType::Dot => "'.'",
+            TokenType::Minus => "'-'",
+            TokenType::Plus => "'+'",
+            TokenType::Semicolon => "';'",
+            TokenType::Slash => "'/'",
+            TokenType::Star => "'*'",
+            TokenType::Question => "'?'",
+            TokenType::Colon => "':'",
+            TokenType::Bang => "'!'",
+            TokenType::BangEqual => "'!='",
+            TokenType::Equal => "'='",
+            TokenType::EqualEqual => "'=='",
+            TokenType::Greater => "'>'",
+            TokenType::GreaterEqual => "'>='",
+            TokenType::Less => "'<'",
+            TokenType::LessEqual => "'<='",
+            TokenType::Identifier => "identifier",
+            TokenType::String => "string",
+            TokenType::Number => "number",
+            TokenType::StringStart => "string",
+            TokenType::StringMiddle | TokenType::StringEnd => "'}'",
+            TokenType::And => "'and'",
+            TokenType::Class => "'class'",
+            TokenType::Else => "'else'",
+            TokenType::False => "'false'",
+            TokenType::Fun => "'fun'",
+            TokenType::For => "'for'",
+            TokenType::If => "'if'",
+            TokenType::Nil => "'nil'",
+            TokenType::Or => "'or'",
+            TokenType::Print => "'print'",
+            TokenType::Return => "'return'",
+            TokenType::Super => "'super'",
+            TokenType::This => "'this'",
+            TokenType::True => "'true'",
+            TokenType::Var => "'var'",
+            TokenType::While => "'while'",
+            TokenType::Eof => "end of file",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+pub fn pretty(tokens: &[Token]) -> String {
     let mut string_builder = String::new();
     let mut current_line = 1;
     for token in tokens {
@@ -404,7 +1406,7 @@ pub fn pretty(tokens: &Vec<Token>) -> String {
             current_line = token.line;
         }
         string_builder.push_str(&format!("[{:?}]", match &token.literal {
-            Some(LiteralValue::String(s)) => s.clone(),
+            Some(LiteralValue::String(s)) => s.to_string(),
             Some(LiteralValue::Number(n)) => format!("{:?}", n),
             _ => format!("{:?}", token.token_type),
         }));
@@ -427,10 +1429,11 @@ mod tests {
         init_logger();
         let token = Token::new(
             TokenType::Identifier,
-            "test".to_string(),
+            "test",
             Some(LiteralValue::String("test".to_string())),
             1,
             1,
+            Span::new(0, 4),
         );
         assert_eq!(format!("{:?}", token), "Identifier(1,1)'test'[String(\"test\")]");
     }
@@ -497,6 +1500,35 @@ mod tests {
         assert_eq!(tokens[0].line, 3);
     }
 
+    #[test]
+    fn test_nested_comment() {
+        init_logger();
+        let source = "/* a /* b\n*/ c */ x /**/ /* * / */ y";
+        let tokens: Vec<Token> = scan(source).collect();
+        let lexemes: Vec<_> = tokens.iter().map(|token| token.lexeme).collect();
+        assert_eq!(lexemes, ["x", "y", ""]);
+        // Positions after a comment match those of the same amount of plain text.
+        let plain: Vec<Token> = scan("         \n        x").collect();
+        assert_eq!((tokens[0].line, tokens[0].character), (2, plain[0].character));
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        init_logger();
+        let source = "print 1;\n/* outer /* inner */\nprint 2;";
+        let results: Vec<_> = Scanner::new(source).collect();
+        let error = results.iter().find_map(|result| result.as_ref().err()).unwrap();
+        assert_eq!(error, &ScannerError::UnterminatedComment {
+            line: 2,
+            span: Span::new(9, source.len()),
+        });
+        assert_eq!(results.last().unwrap().as_ref().unwrap().token_type, TokenType::Eof);
+
+        let diagnostic = Diagnostic::from(error);
+        assert_eq!(diagnostic.code, Some("E0006"));
+        assert_eq!(diagnostic.primary_span(), Some(Span::new(9, 11)));
+    }
+
     #[test]
     fn test_lexeme() {
         init_logger();
@@ -529,4 +1561,474 @@ mod tests {
         assert_eq!(tokens[7].token_type, TokenType::BangEqual);
         assert_eq!(tokens[7].lexeme, "!=");
     }
+
+    #[test]
+    fn test_spans() {
+        init_logger();
+        let source = "var x = \"ü\";\n  y";
+        let tokens: Vec<Token> = scan(source).collect();
+        for token in &tokens {
+            assert_eq!(&source[token.span.start..token.span.end], token.lexeme);
+        }
+        assert_eq!(tokens[1].span, Span::new(4, 5));
+        assert_eq!(tokens[3].span, Span::new(8, 12));
+        assert_eq!(tokens[5].span, Span::new(16, 17));
+        assert_eq!(tokens[6].span, Span::empty(source.len()));
+    }
+
+    #[test]
+    fn test_errors() {
+        init_logger();
+        let results: Vec<_> = Scanner::new("var a = 1 @ 2;\nprint \"open").collect();
+        let errors: Vec<_> = results.iter().filter_map(|result| result.clone().err()).collect();
+        assert_eq!(errors, [
+            ScannerError::UnexpectedCharacter {
+                character: '@',
+                line: 1,
+                span: Span::new(10, 11),
+            },
+            ScannerError::UnterminatedString {
+                line: 2,
+                span: Span::new(21, 26),
+            },
+        ]);
+        // Errors come out between the tokens they were found between.
+        assert_eq!(results[3].as_ref().unwrap().lexeme, "1");
+        assert!(results[4].is_err());
+        assert_eq!(results[5].as_ref().unwrap().lexeme, "2");
+        assert_eq!(results.last().unwrap().as_ref().unwrap().token_type, TokenType::Eof);
+
+        assert_eq!(errors[0].to_string(), "[line 1] Error: Unexpected character '@'.");
+        let diagnostic = Diagnostic::from(&errors[1]);
+        assert_eq!(diagnostic.code, Some("E0002"));
+        assert_eq!(diagnostic.primary_span(), Some(Span::new(21, 22)));
+    }
+
+    #[test]
+    fn test_lazy() {
+        init_logger();
+        let mut scanner = Scanner::new("print 1; @ print \"unterminated");
+        assert_eq!(scanner.next().unwrap().unwrap().token_type, TokenType::Print);
+        // Nothing past the first token has been looked at yet.
+        assert_eq!(scanner.offset, 5);
+
+        let first_error =
+            Scanner::new("print 1; @ print \"unterminated").collect::<Result<Vec<_>, _>>();
+        assert!(matches!(
+            first_error,
+            Err(ScannerError::UnexpectedCharacter {
+                character: '@',
+                ..
+            })
+        ));
+
+        let mut scanner = Scanner::new("");
+        assert_eq!(scanner.next().unwrap().unwrap().token_type, TokenType::Eof);
+        assert!(scanner.next().is_none());
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn test_lexemes_borrow_source() {
+        init_logger();
+        let source = "var s = \"größe\";";
+        let tokens: Vec<Token> = scan(source).collect();
+        for token in &tokens {
+            assert_eq!(token.lexeme, &source[token.span.start..token.span.end]);
+            assert!(
+                source.as_bytes().as_ptr_range().contains(&token.lexeme.as_ptr())
+                    || token.lexeme.is_empty()
+            );
+        }
+        let mut interner = Interner::new();
+        let owned = tokens[3].to_owned_token(&mut interner);
+        assert_eq!(owned.lexeme, "\"größe\"");
+        assert_eq!(owned.literal, Some(LiteralValue::String(interner.intern("größe"))));
+        assert_eq!(owned.span, tokens[3].span);
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        init_logger();
+        let source = r#""a\n\t\r\0\"\\\u{48}\u{1F600}""#;
+        let tokens: Vec<Token> = scan(source).collect();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].lexeme, source);
+        assert_eq!(
+            tokens[0].literal,
+            Some(LiteralValue::String("a\n\t\r\0\"\\H\u{1F600}".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        init_logger();
+        let source = r#"print "a\qb\u{110000}c\u{12" + "\u41";"#;
+        let results: Vec<_> = Scanner::new(source).collect();
+        let errors: Vec<_> = results.iter().filter_map(|result| result.clone().err()).collect();
+        let escape = |text: &str| {
+            let start = source.find(text).unwrap();
+            Span::new(start, start + text.len())
+        };
+        assert_eq!(errors, [
+            ScannerError::InvalidEscape {
+                escape: r"\q".to_string(),
+                line: 1,
+                span: escape(r"\q"),
+            },
+            ScannerError::InvalidCodePoint {
+                value: 0x110000,
+                line: 1,
+                span: escape(r"\u{110000}"),
+            },
+            ScannerError::InvalidEscape {
+                escape: r"\u{12".to_string(),
+                line: 1,
+                span: escape(r"\u{12"),
+            },
+            // `\u` without a brace stops right after the `u`.
+            ScannerError::InvalidEscape {
+                escape: r"\u".to_string(),
+                line: 1,
+                span: Span::new(escape(r"\u4").start, escape(r"\u4").end - 1),
+            },
+        ]);
+        // Strings with bad escapes still become tokens, after their errors.
+        let tokens: Vec<_> = results.into_iter().filter_map(Result::ok).collect();
+        assert_eq!(tokens[1].literal, Some(LiteralValue::String("abc".to_string())));
+        assert_eq!(tokens[3].literal, Some(LiteralValue::String("41".to_string())));
+        assert_eq!(
+            errors[1].to_string(),
+            "[line 1] Error: '\\u{110000}' is not a Unicode scalar value."
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        init_logger();
+        let source = r#""a ${b} c ${ "d ${e}" + f } \${g}""#;
+        let tokens: Vec<Token> = scan(source).collect();
+        let kinds: Vec<_> = tokens.iter().map(|token| (&token.token_type, token.lexeme)).collect();
+        assert_eq!(kinds, [
+            (&TokenType::StringStart, "\"a ${"),
+            (&TokenType::Identifier, "b"),
+            (&TokenType::StringMiddle, "} c ${"),
+            (&TokenType::StringStart, "\"d ${"),
+            (&TokenType::Identifier, "e"),
+            (&TokenType::StringEnd, "}\""),
+            (&TokenType::Plus, "+"),
+            (&TokenType::Identifier, "f"),
+            (&TokenType::StringEnd, r#"} \${g}""#),
+            (&TokenType::Eof, ""),
+        ]);
+        assert_eq!(tokens[2].literal, Some(LiteralValue::String(" c ".to_string())));
+        assert_eq!(tokens[8].literal, Some(LiteralValue::String(" ${g}".to_string())));
+    }
+
+    #[test]
+    fn test_number_forms() {
+        init_logger();
+        let source = "0xFF 0XaB 0b1010 0o17 1e10 2.5E-3 1_000_000 0x1F_FFFF_FFFF_FFFF 1.5e+2 0 \
+                      007 1.5.foo";
+        let numbers: Vec<_> = scan(source)
+            .filter_map(|token| token.literal.map(|literal| (token.lexeme, literal)))
+            .collect();
+        let expected = [
+            ("0xFF", 255.0),
+            ("0XaB", 171.0),
+            ("0b1010", 10.0),
+            ("0o17", 15.0),
+            ("1e10", 1e10),
+            ("2.5E-3", 2.5e-3),
+            ("1_000_000", 1_000_000.0),
+            ("0x1F_FFFF_FFFF_FFFF", 9_007_199_254_740_991.0),
+            ("1.5e+2", 150.0),
+            ("0", 0.0),
+            ("007", 7.0),
+            ("1.5", 1.5),
+        ];
+        assert_eq!(numbers, expected.map(|(lexeme, value)| (lexeme, LiteralValue::Number(value))));
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        init_logger();
+        fn error(source: &str) -> (NumberError, &str) {
+            match Scanner::new(source).next() {
+                Some(Err(ScannerError::InvalidNumber {
+                    error,
+                    span,
+                    ..
+                })) => (error, &source[span.start..span.end]),
+                other => panic!("expected a number error for {}, got {:?}", source, other),
+            }
+        }
+        assert_eq!(error("0x"), (NumberError::MissingDigits, "0x"));
+        assert_eq!(error("0b_1"), (NumberError::MisplacedSeparator, "_"));
+        assert_eq!(error("1__0"), (NumberError::MisplacedSeparator, "__"));
+        assert_eq!(error("1_"), (NumberError::MisplacedSeparator, "_"));
+        assert_eq!(error("1_.5"), (NumberError::MisplacedSeparator, "_"));
+        assert_eq!(error("1e"), (NumberError::MissingExponent, "e"));
+        assert_eq!(error("1e+"), (NumberError::MissingExponent, "e"));
+        assert_eq!(
+            error("0b102"),
+            (
+                NumberError::InvalidDigit {
+                    digit: '2',
+                    radix: 2
+                },
+                "2"
+            )
+        );
+        assert_eq!(
+            error("12abc"),
+            (
+                NumberError::InvalidDigit {
+                    digit: 'a',
+                    radix: 10
+                },
+                "a"
+            )
+        );
+        assert_eq!(error("1e999"), (NumberError::TooLarge, "1e999"));
+        assert_eq!(
+            ScannerError::InvalidNumber {
+                error: NumberError::MisplacedSeparator,
+                line: 3,
+                span: Span::default(),
+            }
+            .to_string(),
+            "[line 3] Error: Digit separator '_' must be between two digits."
+        );
+
+        // The malformed literal is skipped as a whole.
+        let tokens: Vec<_> = scan("1__0 + 0x;").map(|token| token.token_type).collect();
+        assert_eq!(tokens, [TokenType::Plus, TokenType::Semicolon, TokenType::Eof]);
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        init_logger();
+        let tokens: Vec<Token> = scan("_private 名前 größe x1_2 ℮").collect();
+        let kinds: Vec<_> = tokens.iter().map(|token| (&token.token_type, token.lexeme)).collect();
+        assert_eq!(kinds, [
+            (&TokenType::Identifier, "_private"),
+            (&TokenType::Identifier, "名前"),
+            (&TokenType::Identifier, "größe"),
+            (&TokenType::Identifier, "x1_2"),
+            (&TokenType::Identifier, "℮"),
+            (&TokenType::Eof, ""),
+        ]);
+        // Not XID_Start.
+        assert!(matches!(
+            Scanner::new("€").next(),
+            Some(Err(ScannerError::UnexpectedCharacter {
+                character: '€',
+                ..
+            }))
+        ));
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn test_identifiers_normalize_to_nfc() {
+        init_logger();
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let source = format!("{} {}", composed, decomposed);
+        let tokens: Vec<Token> = scan(&source).collect();
+        // Lexemes stay as written; owned tokens carry the normalized name.
+        assert_eq!(tokens[1].lexeme, decomposed);
+        let mut interner = Interner::new();
+        let names: Vec<_> =
+            tokens[..2].iter().map(|token| token.to_owned_token(&mut interner)).collect();
+        for name in names {
+            assert_eq!(name.lexeme, composed);
+            assert_eq!(interner.resolve(name.symbol()), composed);
+        }
+    }
+
+    /// Everything the lossless scanner produced for `source`, put back
+    /// together.
+    fn reassemble(source: &str) -> String {
+        let mut text = String::new();
+        for token in Scanner::lossless(source).filter_map(Result::ok) {
+            let leading = token.leading_trivia.iter().map(|trivia| trivia.text);
+            let trailing = token.trailing_trivia.iter().map(|trivia| trivia.text);
+            text.extend(leading.chain([token.lexeme]).chain(trailing));
+        }
+        text
+    }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        init_logger();
+        let sources = [
+            "",
+            "  \t\r\n",
+            "var x = 1; // one\r\n/// doc\nprint x;",
+            "/** doc */ fun f() { /* a /* b */ */ return \"${ f( ) } \\t\"; }\n",
+            "print 1 @ 2; 0x; 1__0 \"bad \\q escape\";",
+            "print 1; /* trailing but unclosed\n",
+            "print \"unterminated ${ 1 + 2 } string",
+            "/**/ /***/ //// not docs\n",
+        ];
+        for source in sources {
+            assert_eq!(reassemble(source), source);
+        }
+    }
+
+    #[test]
+    fn test_trivia_attachment() {
+        init_logger();
+        let source = "var x = 1;  // note\n\n/// doc\nprint /* here */ x; /* open";
+        let results: Vec<_> = Scanner::lossless(source).collect();
+        let tokens: Vec<_> = results.iter().filter_map(|result| result.as_ref().ok()).collect();
+        let kinds = |trivia: &[Trivia]| trivia.iter().map(|trivia| trivia.kind).collect::<Vec<_>>();
+
+        assert_eq!(tokens[4].lexeme, ";");
+        assert_eq!(kinds(&tokens[4].trailing_trivia), [
+            TriviaKind::Whitespace,
+            TriviaKind::LineComment
+        ]);
+        assert_eq!(tokens[4].trailing_trivia[0].text, "  ");
+        assert_eq!(tokens[5].lexeme, "print");
+        assert_eq!(kinds(&tokens[5].leading_trivia), [
+            TriviaKind::Newline,
+            TriviaKind::Newline,
+            TriviaKind::DocComment,
+            TriviaKind::Newline
+        ]);
+        assert_eq!(tokens[5].leading_trivia[2].text, "/// doc");
+        assert_eq!(kinds(&tokens[5].trailing_trivia), [
+            TriviaKind::Whitespace,
+            TriviaKind::BlockComment,
+            TriviaKind::Whitespace
+        ]);
+
+        // The unclosed comment is left to lead the end of file, and reported.
+        let eof = tokens.last().unwrap();
+        assert!(
+            tokens[7].trailing_trivia.iter().all(|trivia| trivia.kind == TriviaKind::Whitespace)
+        );
+        assert_eq!(kinds(&eof.leading_trivia), [TriviaKind::BlockComment]);
+        assert!(
+            results
+                .iter()
+                .any(|result| matches!(result, Err(ScannerError::UnterminatedComment { .. })))
+        );
+
+        // Only the lossless scanner keeps trivia.
+        assert!(
+            scan(source)
+                .all(|token| token.leading_trivia.is_empty() && token.trailing_trivia.is_empty())
+        );
+    }
+
+    /// Relexes `tokens`, the tokens of `old_source`, after `edit`, checking
+    /// that it gives what scanning from scratch does, and that patching a
+    /// copy of the old tokens with what changed does too. Returns the edited
+    /// source and what changed.
+    fn assert_relex(
+        tokens: &mut TokenStore,
+        old_source: &str,
+        edit: TextEdit,
+    ) -> (String, Relexed) {
+        let source = edit.apply(old_source);
+        let mut patched: Vec<_> = tokens.iter().collect();
+        let relexed = Scanner::relex(&source, tokens, &edit);
+        let scanned: Vec<_> = TokenStore::new(&source).iter().collect();
+        assert_eq!(tokens.iter().collect::<Vec<_>>(), scanned, "{:?} after {:?}", source, edit);
+
+        // As the playground editor does it, which only keeps where tokens
+        // start.
+        let shift = relexed.shift;
+        let line = patched.get(relexed.replaced.end).map(|token| token.start_line);
+        for token in &mut patched[relexed.replaced.end..] {
+            if Some(token.start_line) == line {
+                token.start_column = token.start_column.wrapping_add_signed(shift.columns);
+            }
+            token.start_line = token.start_line.wrapping_add_signed(shift.lines);
+            token.span.start = token.span.start.wrapping_add_signed(shift.bytes);
+            token.span.end = token.span.end.wrapping_add_signed(shift.bytes);
+        }
+        patched.splice(
+            relexed.replaced.clone(),
+            relexed.inserted.clone().map(|index| scanned[index].clone()),
+        );
+        let starts = |tokens: &[KeptToken]| {
+            tokens
+                .iter()
+                .map(|token| (token.span, token.start_line, token.start_column))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(starts(&patched), starts(&scanned), "{:?} after {:?}", source, edit);
+        (source, relexed)
+    }
+
+    #[test]
+    fn test_relex() {
+        init_logger();
+        let source = "var x = \"a ${ f({y}) } b\"; /* c /* d */ */\n\
+                      print x.y + 1.5e3; // end\n\
+                      fun f() { return \"s\\n\"; }\n";
+        let insertions = ["\"", "*", "/", "{", "}", "$", "1", "a", "\n", " ", "é", "/*", "*/"];
+        let boundaries = source.char_indices().map(|(offset, _)| offset).chain([source.len()]);
+        for offset in boundaries {
+            for text in insertions {
+                let edit = TextEdit::new(Span::empty(offset), text);
+                assert_relex(&mut TokenStore::new(source), source, edit);
+            }
+            if let Some(c) = source[offset..].chars().next() {
+                let edit = TextEdit::new(Span::new(offset, offset + c.len_utf8()), "");
+                assert_relex(&mut TokenStore::new(source), source, edit);
+            }
+        }
+        let edit = TextEdit::new(Span::new(0, source.len()), "print 1;");
+        assert_relex(&mut TokenStore::new(source), source, edit);
+        assert_relex(&mut TokenStore::default(), "", TextEdit::new(Span::empty(0), source));
+    }
+
+    #[test]
+    fn test_relex_edits() {
+        init_logger();
+        let mut source = "var a = 1;\nprint a + 2;\nvar b = \"${a}\";\nprint b;\n".to_string();
+        let mut tokens = TokenStore::new(&source);
+        // Renaming a variable rescans it and the token before it, and moves
+        // the rest of its line along.
+        let a = source.find("a +").unwrap();
+        let relexed;
+        (source, relexed) =
+            assert_relex(&mut tokens, &source, TextEdit::new(Span::new(a, a + 1), "alpha"));
+        assert_eq!(relexed, Relexed {
+            replaced: 5..7,
+            inserted: 5..7,
+            shift: Shift {
+                bytes: 4,
+                lines: 0,
+                columns: 4,
+            },
+        });
+
+        // Edits before, inside and after the tokens still to be moved by
+        // earlier ones, adding lines and interpolations. Each only rescans
+        // the tokens next to it.
+        let edits = [
+            (source.len(), "\n"),
+            (source.find("${a}").unwrap() + 2, "a + "),
+            (0, "// first\n\n"),
+            (source.find("print b").unwrap(), "{\n"),
+            (source.find("2;").unwrap(), "\"x\ny\" + "),
+        ];
+        for (offset, text) in edits.into_iter().rev() {
+            let relexed;
+            (source, relexed) =
+                assert_relex(&mut tokens, &source, TextEdit::new(Span::empty(offset), text));
+            assert!(relexed.replaced.len() <= 3, "{:?} for {:?}", relexed, text);
+        }
+        let start = source.find("first").unwrap();
+        let end = source.find("b =").unwrap();
+        (source, _) = assert_relex(&mut tokens, &source, TextEdit::new(Span::new(start, end), ""));
+        assert_relex(&mut tokens, &source, TextEdit::new(Span::new(0, source.len()), ""));
+    }
 }
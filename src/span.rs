@@ -0,0 +1,252 @@
+use std::{fmt::Display, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::{LiteralValue, OwnedToken, Token};
+
+/// A half-open range of byte offsets into the original source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span {
+            start,
+            end,
+        }
+    }
+
+    /// An empty span just before `offset`.
+    pub fn empty(offset: usize) -> Self {
+        Span::new(offset, offset)
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn merge(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// Anything that covers a known range of the source.
+pub trait Spanned {
+    fn span(&self) -> Span;
+}
+
+/// Like [`Spanned`], for AST fields that may not carry a position at all,
+/// such as literal values or an absent `else` branch. Used by `lox_ast!` to
+/// compute node spans from their fields.
+pub trait MaybeSpanned {
+    fn maybe_span(&self) -> Option<Span>;
+}
+
+impl Spanned for Token<'_> {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Spanned for OwnedToken {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl MaybeSpanned for OwnedToken {
+    fn maybe_span(&self) -> Option<Span> {
+        Some(self.span)
+    }
+}
+
+impl MaybeSpanned for Span {
+    fn maybe_span(&self) -> Option<Span> {
+        Some(*self)
+    }
+}
+
+impl MaybeSpanned for LiteralValue {
+    fn maybe_span(&self) -> Option<Span> {
+        None
+    }
+}
+
+impl<T: MaybeSpanned> MaybeSpanned for Box<T> {
+    fn maybe_span(&self) -> Option<Span> {
+        self.as_ref().maybe_span()
+    }
+}
+
+impl<T: MaybeSpanned> MaybeSpanned for Rc<T> {
+    fn maybe_span(&self) -> Option<Span> {
+        self.as_ref().maybe_span()
+    }
+}
+
+impl<T: MaybeSpanned> MaybeSpanned for Option<T> {
+    fn maybe_span(&self) -> Option<Span> {
+        self.as_ref().and_then(MaybeSpanned::maybe_span)
+    }
+}
+
+impl<T: MaybeSpanned> MaybeSpanned for Vec<T> {
+    fn maybe_span(&self) -> Option<Span> {
+        self.iter().filter_map(MaybeSpanned::maybe_span).reduce(Span::merge)
+    }
+}
+
+/// A 1-based line and column. Columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Converts byte offsets into line and column numbers for a single source.
+pub struct SourceMap<'src> {
+    source: &'src str,
+    /// Byte offset at which each line starts.
+    line_starts: Vec<usize>,
+}
+
+impl<'src> SourceMap<'src> {
+    pub fn new(source: &'src str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        SourceMap {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Offsets past the end of the source are clamped to it.
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = self.floor_char_boundary(offset);
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.line_starts[line]..offset].chars().count() + 1;
+        Location {
+            line: line + 1,
+            column,
+        }
+    }
+
+    #[cfg(test)]
+    fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The text of the 1-based `line`, without its line terminator.
+    pub fn line_text(&self, line: usize) -> Option<&'src str> {
+        let span = self.line_span(line)?;
+        Some(&self.source[span.start..span.end])
+    }
+
+    /// The span of the 1-based `line`, without its line terminator.
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map(|next| next - 1).unwrap_or(self.source.len());
+        let end = if self.source[start..end].ends_with('\r') { end - 1 } else { end };
+        Some(Span::new(start, end))
+    }
+
+    fn floor_char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+/// A change to a source: the bytes in `span` replaced by `text`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span, text: impl Into<String>) -> Self {
+        TextEdit {
+            span,
+            text: text.into(),
+        }
+    }
+
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.text.len() - self.span.len());
+        edited.push_str(&source[..self.span.start]);
+        edited.push_str(&self.text);
+        edited.push_str(&source[self.span.end..]);
+        edited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(Span::new(4, 6).merge(Span::new(1, 2)), Span::new(1, 6));
+        assert_eq!(Span::new(1, 9).merge(Span::new(3, 4)), Span::new(1, 9));
+    }
+
+    #[test]
+    fn test_location() {
+        let source = "var a;\nprint \"é\" + a;\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.location(0), Location {
+            line: 1,
+            column: 1
+        });
+        assert_eq!(map.location(7), Location {
+            line: 2,
+            column: 1
+        });
+        // `+` comes after the two-byte `é`, so its column is one less than its
+        // byte offset into the line.
+        let plus = source.find('+').unwrap();
+        assert_eq!(map.location(plus), Location {
+            line: 2,
+            column: 11
+        });
+        assert_eq!(map.location(source.len() + 10), Location {
+            line: 3,
+            column: 1
+        });
+    }
+
+    #[test]
+    fn test_line_text() {
+        let map = SourceMap::new("first\r\nsecond\nthird");
+        assert_eq!(map.line_count(), 3);
+        assert_eq!(map.line_text(1), Some("first"));
+        assert_eq!(map.line_text(2), Some("second"));
+        assert_eq!(map.line_text(3), Some("third"));
+        assert_eq!(map.line_text(0), None);
+        assert_eq!(map.line_text(4), None);
+    }
+}